@@ -0,0 +1,644 @@
+// Item based splits. None of this is compiled into the doors-only build.
+
+use asr::{settings::Gui, watcher::Watcher};
+
+use crate::{Settings, Watchers, EMPTY_SLOT};
+
+const VIP_CARDS: [u16; 5] = [303, 304, 302, 305, 306];
+const PANELS: [u16; 4] = [359, 364, 366, 368];
+const CLOCK_HANDS: [u16; 2] = [331, 332];
+const LAB_KEYS: [u16; 2] = [428, 429];
+
+// Items made by combining others, with the components they replace in the inventory. While the
+// combined item is held its components count as held too, so combining them doesn't look like
+// they were used up or lost. None of the combined IDs have been confirmed yet, add them here as
+// (combined, components) once they are.
+#[cfg(not(test))]
+const COMBINED: &[(u16, &[u16])] = &[];
+// A made-up ID standing in for the combined clock hands, so the tests run the combine code
+#[cfg(test)]
+const COMBINED: &[(u16, &[u16])] = &[(tests::CLOCK, &CLOCK_HANDS)];
+
+// Reads the setting an entry of one of the item tables is toggled by
+type Toggle = fn(&Settings) -> bool;
+
+// Builds the KEY_ITEMS table from setting/ID pairs, along with a way for presets to switch
+// every one of those settings at once
+macro_rules! key_items {
+    ($($setting:ident: $item:literal,)*) => {
+        const KEY_ITEMS: &[(Toggle, u16)] = &[$((|settings| settings.$setting, $item)),*];
+
+        fn set_key_items(settings: &mut Settings, enabled: impl Fn(u16) -> bool) {
+            $(settings.$setting = enabled($item);)*
+        }
+    };
+}
+
+// Every key item with its own pickup split, in the order the settings list them
+key_items! {
+    keno: 309,
+    susie: 303,
+    nancy: 304,
+    cheryl: 302,
+    stagekey: 310,
+    leagan: 305,
+    attract: 335,
+    museum: 336,
+    moon: 337,
+    evil: 340,
+    spear: 308,
+    cardc: 338,
+    cardd: 339,
+    sydney: 306,
+    card9: 311,
+    bluehand: 331,
+    redhand: 332,
+    panel1: 359,
+    event: 363,
+    panel2: 364,
+    panel4: 366,
+    panel6: 368,
+    ykey: 343,
+    d4: 383,
+    lot: 385,
+    camp: 392,
+    small: 393,
+    fork: 434,
+    log: 408,
+    guest: 435,
+    shower: 413,
+    shelf: 403,
+    bourbon: 415,
+    marlin: 405,
+    chain: 404,
+    observ: 428,
+    sterile: 429,
+    // Unverified: 111 is the only ID outside the 3xx/4xx key item range, and it hasn't been
+    // checked against the other items' IDs in a real inventory, so it may be shared with one
+    // of them. If it is, narrow this check with the quantity field or add 111 to ignored_items.
+    m8: 111,
+    sin: 423,
+    fuse: 430,
+}
+
+// The key items split on by the Any% preset, in the order the route picks them up. The optional
+// pickups (Keno Ticket, No.9 Playing Card, Bourbon and the M82A1) are left out.
+const ANY_PERCENT: [u16; 36] = [
+    303, 304, 302, 310, 305, 335, 336, 337, 340, 308, 338, 339, 306, 331, 332, 359, 363, 364, 366,
+    368, 343, 383, 385, 392, 393, 434, 408, 435, 413, 403, 405, 404, 428, 429, 423, 430,
+];
+
+// Two settings watching the same ID would always split together, and one watching the empty
+// slot's ID would split on every free slot, so either of those in the table fails the build. So
+// does a table with more entries than the collected/used/entered sets have bits, see key_bit().
+const _: () = {
+    assert!(KEY_ITEMS.len() <= 64, "more key items than a set has bits");
+    let mut i = 0;
+    while i < KEY_ITEMS.len() {
+        assert!(
+            KEY_ITEMS[i].1 != EMPTY_SLOT,
+            "a key item has the empty slot's ID"
+        );
+        let mut j = i + 1;
+        while j < KEY_ITEMS.len() {
+            assert!(
+                KEY_ITEMS[i].1 != KEY_ITEMS[j].1,
+                "two key items share an ID"
+            );
+            j += 1;
+        }
+        i += 1;
+    }
+};
+
+// Items that can split on every pickup. Only increases of the total held count, so using one
+// up never splits.
+const EACH_PICKUP: [(Toggle, u16); 1] = [(|settings| settings.bourbon_each, 415)];
+
+// Rooms split on when first entered after their key was used, going by the key item's entered
+// bit. Which map each key opens isn't known, so it's whichever room comes next.
+const ROOM_ENTRIES: [(Toggle, u16); 12] = [
+    (|settings| settings.evil_house, 340),
+    (|settings| settings.show_stage, 310),
+    (|settings| settings.attraction_area, 335),
+    (|settings| settings.museum_area, 336),
+    (|settings| settings.spear_area, 308),
+    (|settings| settings.event_room, 363),
+    (|settings| settings.campground, 392),
+    (|settings| settings.forklift, 434),
+    (|settings| settings.guesthouse, 435),
+    (|settings| settings.moon_room, 337),
+    (|settings| settings.marlin_gate, 405),
+    // No power flag is known, so the fuse leaving the inventory is taken as it being put in and
+    // the next room entered as the one the power opened up
+    (|settings| settings.fuse_power, 430),
+];
+
+// Keys of the park's three main areas (Attraction, Museum, Desert Moon)
+const PARK_AREAS: [u16; 3] = [335, 336, 337];
+
+// Per-run progress of the splits that need more than the current inventory
+#[derive(Default)]
+pub(crate) struct ItemProgress {
+    vip_cards: Watcher<bool>,
+    panels: Watcher<bool>,
+    clock_hands: Watcher<bool>,
+    lab_keys: Watcher<bool>,
+    lab_access: Watcher<bool>,
+    // One bit per entry of KEY_ITEMS, so slot order and duplicate slots don't matter
+    collected: u64,
+    collected_count: Watcher<u32>,
+    // Same layout, for the key items that have left the inventory after being held, and for
+    // the ones whose next room has been entered after that
+    used: Watcher<u64>,
+    entered: Watcher<u64>,
+    // Most ammo the M82A1 has held this run, so firing it never counts against a target
+    m8_peak_ammo: Watcher<u16>,
+}
+
+#[derive(Gui, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum KeySplit {
+    /// Picked up
+    #[default]
+    Pickup,
+    /// Used (leaves the inventory)
+    Use,
+    /// Used, on entering the next room
+    Entry,
+}
+
+#[derive(Gui, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum AmmoTarget {
+    /// Off
+    #[default]
+    Off,
+    /// Loaded (any ammo)
+    Loaded,
+    /// 5 rounds
+    Five,
+    /// 10 rounds
+    Ten,
+}
+
+impl AmmoTarget {
+    fn count(self) -> Option<u16> {
+        match self {
+            Self::Off => None,
+            Self::Loaded => Some(1),
+            Self::Five => Some(5),
+            Self::Ten => Some(10),
+        }
+    }
+}
+
+#[derive(Gui, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum KeyItemTarget {
+    /// Off
+    #[default]
+    Off,
+    /// 10 key items
+    Ten,
+    /// 20 key items
+    Twenty,
+    /// 30 key items
+    Thirty,
+    /// Every key item
+    All,
+}
+
+impl KeyItemTarget {
+    fn count(self) -> Option<u32> {
+        match self {
+            Self::Off => None,
+            Self::Ten => Some(10),
+            Self::Twenty => Some(20),
+            Self::Thirty => Some(30),
+            Self::All => Some(KEY_ITEMS.len() as u32),
+        }
+    }
+}
+
+pub(crate) fn update(watchers: &mut Watchers) {
+    let progress = &mut watchers.run.items;
+    // Loading a save or dying puts the inventory back the way it was, which can take keys out of
+    // it that were never used. Removals during and right after a rewind are left alone.
+    let rewinding = watchers.run.rewind_age.is_some();
+    collect_set(&mut progress.vip_cards, &watchers.inventory, &VIP_CARDS);
+    collect_set(&mut progress.panels, &watchers.inventory, &PANELS);
+    collect_set(&mut progress.clock_hands, &watchers.inventory, &CLOCK_HANDS);
+    collect_set(&mut progress.lab_keys, &watchers.inventory, &LAB_KEYS);
+    if watchers.game_confirmed {
+        if let Some(inventory) = &watchers.inventory.pair {
+            for item in inventory.current {
+                if let Some(index) = KEY_ITEMS.iter().position(|&(_, id)| id == item) {
+                    progress.collected |= 1 << index;
+                }
+            }
+        }
+        progress
+            .collected_count
+            .update_infallible(progress.collected.count_ones());
+
+        // A key item counts as entered on the first map change after the update it was used on
+        let used = progress.used.pair.map_or(0, |pair| pair.current);
+        let mut entered = progress.entered.pair.map_or(0, |pair| pair.current);
+        if watchers.map_id.pair.is_some_and(|i| i.changed()) {
+            entered |= used;
+        }
+        progress.entered.update_infallible(entered);
+        let removed = if rewinding {
+            0
+        } else {
+            removed_key_items(&watchers.inventory)
+        };
+        progress.used.update_infallible(used | removed);
+
+        // Unverified: a weapon's quantity field is taken to hold the ammo loaded in it
+        if let Some((inventory, quantities)) = watchers.inventory.pair.zip(watchers.quantities.pair)
+        {
+            let ammo = item_quantity(&inventory.current, &quantities.current, 111);
+            let peak = progress.m8_peak_ammo.pair.map_or(0, |pair| pair.current);
+            progress.m8_peak_ammo.update_infallible(peak.max(ammo));
+        }
+    }
+
+    // The lab opens up with both of its keys in hand, or as soon as the Sterilization
+    // Passageway is entered with its key, whichever comes first
+    let unlocked = [
+        progress.lab_access.pair.is_some_and(|pair| pair.current),
+        progress.lab_keys.pair.is_some_and(|pair| pair.current),
+        progress
+            .entered
+            .pair
+            .is_some_and(|pair| pair.current & key_bit(429) != 0),
+    ];
+    progress
+        .lab_access
+        .update_infallible(unlocked.contains(&true));
+}
+
+// Latches to true the first time every item of the set is held at once, so the set stays
+// collected even if one of the items later leaves the watched slots
+fn collect_set(collected: &mut Watcher<bool>, inventory: &Watcher<[u16; 12]>, set: &[u16]) {
+    let held = inventory
+        .pair
+        .is_some_and(|inventory| set.iter().all(|&item| holds(&inventory.current, item)));
+    let latched = collected.pair.is_some_and(|pair| pair.current);
+    collected.update_infallible(latched || held);
+}
+
+// Whether the item is in the inventory, either itself or as part of a combined item
+fn holds(inventory: &[u16; 12], item: u16) -> bool {
+    item != EMPTY_SLOT && inventory.contains(&item)
+        || COMBINED.iter().any(|&(combined, components)| {
+            components.contains(&item) && inventory.contains(&combined)
+        })
+}
+
+// Bits of the key items that were in the inventory on the previous update but aren't anymore
+fn removed_key_items(inventory: &Watcher<[u16; 12]>) -> u64 {
+    let Some(inventory) = &inventory.pair else {
+        return 0;
+    };
+    KEY_ITEMS
+        .iter()
+        .enumerate()
+        .filter(|&(_, &(_, item))| holds(&inventory.old, item) && !holds(&inventory.current, item))
+        .fold(0, |bits, (index, _)| bits | 1 << index)
+}
+
+// Bits of the key items whose split is turned on
+fn enabled_key_items(settings: &Settings) -> u64 {
+    KEY_ITEMS
+        .iter()
+        .enumerate()
+        .filter(|(_, (enabled, _))| enabled(settings))
+        .fold(0, |bits, (index, _)| bits | 1 << index)
+}
+
+// Bits of the key items opening a room whose entry split is turned on
+fn room_entries(settings: &Settings) -> u64 {
+    ROOM_ENTRIES
+        .iter()
+        .filter(|(enabled, _)| enabled(settings))
+        .fold(0, |bits, &(_, key)| bits | key_bit(key))
+}
+
+// The bit of a key item in the collected/used/entered sets, or none if it isn't in KEY_ITEMS
+fn key_bit(key: u16) -> u64 {
+    KEY_ITEMS
+        .iter()
+        .position(|&(_, item)| item == key)
+        .map_or(0, |index| 1 << index)
+}
+
+// Used by the route presets: turns the pickup splits of the Any% route's key items on or off, and
+// every other item split off
+pub(crate) fn preset(settings: &mut Settings, any_percent: bool) {
+    set_key_items(settings, |item| any_percent && ANY_PERCENT.contains(&item));
+    settings.key_split = KeySplit::Pickup;
+    settings.vip_cards = false;
+    settings.panels = false;
+    settings.clock_hands = false;
+    settings.lab_access = false;
+    settings.key_item_target = KeyItemTarget::Off;
+    settings.moon_room = false;
+    settings.marlin_gate = false;
+    settings.sin_used = false;
+    settings.y_panel = false;
+    settings.fuse_power = false;
+    settings.evil_house = false;
+    settings.show_stage = false;
+    settings.attraction_area = false;
+    settings.museum_area = false;
+    settings.spear_area = false;
+    settings.event_room = false;
+    settings.campground = false;
+    settings.forklift = false;
+    settings.guesthouse = false;
+    settings.park_areas = false;
+    settings.bourbon_each = false;
+    settings.chain_equipped = false;
+    settings.m8_ammo = AmmoTarget::Off;
+}
+
+// The item settings' part of the settings code, in the order the settings list them
+pub(crate) fn choices(settings: &Settings) -> impl Iterator<Item = u8> {
+    [
+        settings.key_split as u8,
+        settings.key_item_target as u8,
+        settings.m8_ammo as u8,
+    ]
+    .into_iter()
+}
+
+pub(crate) fn toggles(settings: &Settings) -> impl Iterator<Item = bool> + '_ {
+    KEY_ITEMS
+        .iter()
+        .map(|&(enabled, _)| enabled(settings))
+        .chain([
+            settings.vip_cards,
+            settings.panels,
+            settings.clock_hands,
+            settings.lab_access,
+            settings.moon_room,
+            settings.marlin_gate,
+            settings.sin_used,
+            settings.y_panel,
+            settings.fuse_power,
+            settings.evil_house,
+            settings.show_stage,
+            settings.attraction_area,
+            settings.museum_area,
+            settings.spear_area,
+            settings.event_room,
+            settings.campground,
+            settings.forklift,
+            settings.guesthouse,
+            settings.park_areas,
+            settings.bourbon_each,
+            settings.chain_equipped,
+        ])
+}
+
+// Counts the item split conditions that fired this update, each on its own, see split() in lib.rs
+pub(crate) fn split(watchers: &Watchers, settings: &Settings) -> u32 {
+    // Item checks only mean anything on values read from the right game, not the values held
+    // while the wrong game is loaded
+    if !watchers.game_confirmed {
+        return 0;
+    }
+
+    let progress = &watchers.run.items;
+    let fired = [
+        settings.vip_cards && progress.vip_cards.pair.is_some_and(|i| i.changed_to(&true)),
+        settings.panels && progress.panels.pair.is_some_and(|i| i.changed_to(&true)),
+        settings.clock_hands
+            && progress
+                .clock_hands
+                .pair
+                .is_some_and(|i| i.changed_to(&true)),
+        settings.lab_access
+            && progress
+                .lab_access
+                .pair
+                .is_some_and(|i| i.changed_to(&true)),
+        // No flag for the code puzzle itself has been found, so the SIN key leaving the
+        // inventory stands in for solving it
+        settings.sin_used && newly_set(&progress.used, key_bit(423)) > 0,
+        // No flag for the finished panel mechanism is known either, so the Y-Shaped Panel Key
+        // leaving the inventory with the full panel set collected stands in for it
+        settings.y_panel
+            && progress.panels.pair.is_some_and(|i| i.current)
+            && newly_set(&progress.used, key_bit(343)) > 0,
+        settings.park_areas && {
+            let areas = PARK_AREAS.iter().fold(0, |bits, &key| bits | key_bit(key));
+            progress
+                .entered
+                .pair
+                .is_some_and(|i| i.old & areas != areas && i.current & areas == areas)
+        },
+        settings.chain_equipped
+            && watchers
+                .inventory
+                .pair
+                .zip(watchers.states.pair)
+                .is_some_and(|(inventory, states)| {
+                    !item_equipped(&inventory.old, &states.old, 404)
+                        && item_equipped(&inventory.current, &states.current, 404)
+                }),
+        settings.m8_ammo.count().is_some_and(|target| {
+            progress
+                .m8_peak_ammo
+                .pair
+                .is_some_and(|i| i.old < target && i.current >= target)
+        }),
+        settings.key_item_target.count().is_some_and(|target| {
+            progress
+                .collected_count
+                .pair
+                .is_some_and(|i| i.old < target && i.current >= target)
+        }),
+    ];
+
+    let pickups = watchers
+        .inventory
+        .pair
+        .zip(watchers.quantities.pair)
+        .map_or(0, |(inventory, quantities)| {
+            EACH_PICKUP
+                .iter()
+                .filter(|&&(enabled, item)| {
+                    enabled(settings)
+                        && item_quantity(&inventory.current, &quantities.current, item)
+                            > item_quantity(&inventory.old, &quantities.old, item)
+                })
+                .count() as u32
+        });
+
+    let key_items = match settings.key_split {
+        KeySplit::Pickup => watchers.inventory.pair.map_or(0, |inventory| {
+            KEY_ITEMS
+                .iter()
+                .filter(|&&(enabled, item)| {
+                    enabled(settings) && inventory.check(|arr| holds(arr, item))
+                })
+                .count() as u32
+        }),
+        KeySplit::Use => newly_set(&progress.used, enabled_key_items(settings)),
+        KeySplit::Entry => newly_set(&progress.entered, enabled_key_items(settings)),
+    };
+
+    fired.into_iter().filter(|&fired| fired).count() as u32
+        + newly_set(&progress.entered, room_entries(settings))
+        + pickups
+        + key_items
+}
+
+// How many of the bits in the mask got set this update
+fn newly_set(bits: &Watcher<u64>, mask: u64) -> u32 {
+    bits.pair
+        .map_or(0, |pair| (pair.current & !pair.old & mask).count_ones())
+}
+
+// Total quantity held of an item, summed over every slot it occupies
+fn item_quantity(inventory: &[u16; 12], quantities: &[u16; 12], item: u16) -> u16 {
+    inventory
+        .iter()
+        .zip(quantities)
+        .filter(|(&id, _)| id == item)
+        .fold(0, |total, (_, &quantity)| total.saturating_add(quantity))
+}
+
+// Whether the slot holding an item has its state flagged, which is taken to mean equipped for
+// weapons. The state field hasn't been mapped out fully, so only zero vs. non-zero is used.
+fn item_equipped(inventory: &[u16; 12], states: &[u16; 12], item: u16) -> bool {
+    inventory
+        .iter()
+        .zip(states)
+        .any(|(&id, &state)| id == item && state != 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{tests::settings, Preset};
+
+    pub(super) const CLOCK: u16 = 499;
+
+    fn inventory(old: &[u16], current: &[u16]) -> Watcher<[u16; 12]> {
+        let slots = |items: &[u16]| core::array::from_fn(|i| items.get(i).copied().unwrap_or(0));
+        let mut inventory = Watcher::new();
+        inventory.update_infallible(slots(old));
+        inventory.update_infallible(slots(current));
+        inventory
+    }
+
+    #[test]
+    fn key_item_removed() {
+        assert_eq!(
+            removed_key_items(&inventory(&[340, 310], &[310])),
+            key_bit(340)
+        );
+        assert_eq!(
+            removed_key_items(&inventory(&[340, 310, 111], &[111])),
+            key_bit(340) | key_bit(310)
+        );
+    }
+
+    #[test]
+    fn key_item_not_removed() {
+        // Moved to another slot
+        assert_eq!(removed_key_items(&inventory(&[340, 310], &[310, 340])), 0);
+        // Picked up
+        assert_eq!(removed_key_items(&inventory(&[310], &[310, 340])), 0);
+        // Not a key item
+        assert_eq!(removed_key_items(&inventory(&[340, 150], &[340])), 0);
+        // Nothing read yet
+        assert_eq!(removed_key_items(&Watcher::new()), 0);
+    }
+
+    #[test]
+    fn combined_items_count_as_held() {
+        let mut clock_hands = Watcher::new();
+        collect_set(&mut clock_hands, &inventory(&[331], &[CLOCK]), &CLOCK_HANDS);
+        assert_eq!(clock_hands.pair.map(|pair| pair.current), Some(true));
+
+        // Combining the hands doesn't use them up, losing the combined item does
+        assert_eq!(removed_key_items(&inventory(&[331, 332], &[CLOCK])), 0);
+        assert_eq!(
+            removed_key_items(&inventory(&[CLOCK], &[])),
+            key_bit(331) | key_bit(332)
+        );
+    }
+
+    #[test]
+    fn any_percent_preset() {
+        let mut settings = settings();
+        settings.start = false;
+        settings.bourbon = true;
+        settings.preset = Preset::AnyPercent;
+        settings.apply_preset();
+
+        assert!(!settings.start);
+        assert!(settings.susie && settings.fuse && settings.sterile);
+        assert!(!settings.bourbon && !settings.m8 && !settings.keno && !settings.card9);
+        assert_eq!(
+            enabled_key_items(&settings).count_ones(),
+            ANY_PERCENT.len() as u32
+        );
+        assert!(ANY_PERCENT.iter().all(|&item| key_bit(item) != 0));
+    }
+
+    #[test]
+    fn empty_slots_never_match() {
+        let empty = [EMPTY_SLOT; 12];
+        assert!(!holds(&empty, EMPTY_SLOT));
+        assert!(KEY_ITEMS
+            .iter()
+            .chain(&EACH_PICKUP)
+            .all(|&(_, item)| !holds(&empty, item)));
+
+        // Every key item's pickup split on, with slots being emptied and staying empty
+        let mut settings = settings();
+        settings.key_split = KeySplit::Pickup;
+        set_key_items(&mut settings, |_| true);
+        let mut watchers = Watchers {
+            game_confirmed: true,
+            ..Watchers::default()
+        };
+        for (old, current) in [(&[309, 340][..], &[][..]), (&[], &[]), (&[], &[309])] {
+            watchers.inventory = inventory(old, current);
+            assert_eq!(split(&watchers, &settings), current.len() as u32);
+        }
+    }
+
+    // The layout check after attaching would turn down an inventory holding one of these
+    #[test]
+    fn key_items_pass_the_layout_check() {
+        assert!(KEY_ITEMS
+            .iter()
+            .chain(&EACH_PICKUP)
+            .chain(&ROOM_ENTRIES)
+            .all(|&(_, item)| crate::known_item(item)));
+    }
+
+    fn unique(ids: impl IntoIterator<Item = u16>) -> bool {
+        let mut seen = std::collections::BTreeSet::new();
+        ids.into_iter().all(|id| seen.insert(id))
+    }
+
+    #[test]
+    fn item_ids_unique() {
+        assert!(unique(KEY_ITEMS.iter().map(|&(_, item)| item)));
+        assert!(unique(ANY_PERCENT));
+        assert!(unique(ROOM_ENTRIES.map(|(_, item)| item)));
+
+        // Each key item's setting switches on that item and no other
+        for &(_, item) in KEY_ITEMS {
+            let mut settings = settings();
+            set_key_items(&mut settings, |other| other == item);
+            assert_eq!(enabled_key_items(&settings), key_bit(item));
+        }
+    }
+}
@@ -1,4 +1,4 @@
-#![no_std]
+#![cfg_attr(not(test), no_std)]
 // #![feature(type_alias_impl_trait, const_async_blocks)]
 #![warn(
     clippy::complexity,
@@ -8,76 +8,290 @@
     clippy::undocumented_unsafe_blocks,
     rust_2018_idioms
 )]
+// Most of the crate is only called from main(), which the host tests leave out
+#![cfg_attr(test, allow(dead_code, unused_imports))]
+
+use core::fmt::{self, Write};
+
+#[cfg(not(feature = "doors-only"))]
+mod items;
+
+// The doors-only build leaves every item split out
+#[cfg(feature = "doors-only")]
+mod items {
+    use crate::{Settings, Watchers};
+
+    pub(crate) fn update(_watchers: &mut Watchers) {}
+
+    pub(crate) fn preset(_settings: &mut Settings, _any_percent: bool) {}
+
+    pub(crate) fn choices(_settings: &Settings) -> impl Iterator<Item = u8> {
+        core::iter::empty()
+    }
+
+    pub(crate) fn toggles(_settings: &Settings) -> impl Iterator<Item = bool> {
+        core::iter::empty()
+    }
+
+    pub(crate) fn split(_watchers: &Watchers, _settings: &Settings) -> u32 {
+        0
+    }
+}
 
 use asr::{
-    emulator::ps1::Emulator,
-    future::{next_tick, retry},
-    time::Duration,
-    time_util::frame_count,
-    timer::{self, TimerState},
-    watcher::Watcher,
-    settings::Gui
-};  
+    emulator::ps1::Emulator, future::next_tick, settings::Gui, time::Duration,
+    time_util::frame_count, timer::TimerState, watcher::Watcher,
+};
 
+// The host tests run without the auto splitter runtime, so they get stand-ins for the parts of it
+// that are called outside of main()
+#[cfg(not(test))]
+use asr as runtime;
+#[cfg(test)]
+use tests::runtime;
+
+use runtime::{time_util::Instant, timer};
+
+#[cfg(test)]
+mod tests;
+
+#[cfg(not(test))]
 asr::panic_handler!();
+#[cfg(not(test))]
 asr::async_main!(stable);
 
+#[cfg(not(test))]
 async fn main() {
-    let mut settings = Settings::register(); 
+    let mut settings = Settings::register();
+    let mut conflict = None;
+    // Kept across re-attaches, so an emulator that never exposes the game is noticed
+    let mut unread_ticks: u32 = 0;
+    // For the manual arm option: the auto start is armed each time the timer gets reset
+    let mut armed = false;
+    let mut last_timer_state = timer::state();
+    let mut auto_started = false;
+    let mut dump_requested = false;
+    let mut update_rate = None;
+    // Stands in for the timer running while in practice mode
+    let mut practicing = false;
+    let ignored_items = ignored_items();
+    let start_map = start_map();
+    let mut watchers = Watchers::default();
 
     loop {
         // Hook to the target process
-        let mut emulator = retry(|| Emulator::attach()).await;
-        let mut watchers = Watchers::default();
+        timer::set_variable("autosplitter_status", "Searching");
+        let mut emulator = attach().await;
+        watchers = carry_run_state(core::mem::take(&mut watchers));
         let offsets = Offsets::new();
+        let mut failed_updates: u32 = 0;
+        let mut failed_reads: u32 = 0;
+        let mut start_latched = false;
 
         loop {
             settings.update();
+            settings.apply_preset();
+            // Only logged when it changes, not on every tick the settings stay that way
+            let current_conflict = settings.conflict();
+            if current_conflict != conflict {
+                if let Some(message) = current_conflict {
+                    asr::print_message(message);
+                }
+                conflict = current_conflict;
+            }
+            set_variable("settings_code", format_args!("{}", SettingsCode(&settings)));
+
+            if update_rate != Some(settings.update_rate) {
+                update_rate = Some(settings.update_rate);
+                asr::set_tick_rate(settings.update_rate.ticks_per_second());
+            }
+
+            practicing &= settings.practice_mode;
+
+            // Ticking the box logs one snapshot, untick and tick it again for another
+            if settings.dump && !dump_requested {
+                dump(&watchers, &settings);
+            }
+            dump_requested = settings.dump;
+
+            let current_timer_state = timer::state();
+            if current_timer_state == TimerState::NotRunning
+                && last_timer_state != TimerState::NotRunning
+            {
+                armed = true;
+            }
+            // A run started by hand gets the same setup as an auto start
+            if current_timer_state == TimerState::Running
+                && last_timer_state == TimerState::NotRunning
+                && !core::mem::take(&mut auto_started)
+                && !settings.practice_mode
+            {
+                asr::print_message("Timer started manually");
+                timer::pause_game_time();
+                begin_run(&mut watchers, &settings);
+            }
+            timer::set_variable("start_mode", if settings.start { "Auto" } else { "Manual" });
+            timer::set_variable(
+                "game_time_source",
+                match settings.timing_mode {
+                    TimingMode::Igt => "IGT",
+                    TimingMode::RtaNoLoads => "Real time without loads",
+                    TimingMode::Rta => "Real time with loads",
+                    TimingMode::RawIgt => "Raw IGT",
+                },
+            );
+            if current_timer_state == TimerState::Ended && last_timer_state != TimerState::Ended {
+                log_run_stats(&watchers);
+            }
+            last_timer_state = current_timer_state;
 
             if !emulator.is_open() {
                 break;
             }
 
             if emulator.update() {
+                failed_updates = 0;
+
                 // Splitting logic. Adapted from OG LiveSplit:
                 // Order of execution
                 // 1. update() will always be run first. There are no conditions on the execution of this action.
                 // 2. If the timer is currently either running or paused, then the isLoading, gameTime, and reset actions will be run.
                 // 3. If reset does not return true, then the split action will be run.
                 // 4. If the timer is currently not running (and not paused), then the start action will be run.
-                update_loop(&emulator, &offsets, &mut watchers);
-
-                let timer_state = timer::state();
-                if timer_state == TimerState::Running || timer_state == TimerState::Paused {
-                    if let Some(is_loading) = is_loading(&watchers, &settings) {
-                        if is_loading {
-                            timer::pause_game_time()
-                        } else {
-                            timer::resume_game_time()
+                let timer_state = timer_state(&settings, practicing);
+                if update_loop(
+                    &emulator,
+                    &offsets,
+                    &mut watchers,
+                    &settings,
+                    &ignored_items,
+                    timer_state,
+                ) {
+                    failed_reads = 0;
+                    // The game code went away and came back, so the game was rebooted or the
+                    // disc swapped. Outside of a run, start over from a fresh attach so nothing
+                    // from before carries over. During a run everything is kept, since that's
+                    // how a disc swap shows up too.
+                    if watchers.left_game && watchers.game_confirmed {
+                        if timer_state == TimerState::NotRunning {
+                            asr::print_message("Game restarted, re-attaching...");
+                            break;
                         }
+                        watchers.left_game = false;
+                    }
+                    if unread_ticks >= UNSUPPORTED_EMULATOR_TICKS {
+                        timer::set_variable("emulator_warning", "");
+                    }
+                    unread_ticks = 0;
+                    // Shown side by side so a drift between the two can be traced back to the
+                    // rewind accounting
+                    if let Some(igt) = &watchers.igt.pair {
+                        set_variable("igt_raw", format_args!("{}", Clock(igt.current)));
                     }
-
                     if let Some(game_time) = game_time(&watchers, &settings) {
-                        timer::set_game_time(game_time)
+                        set_variable("game_time", format_args!("{}", Clock(game_time)));
                     }
-
-                    if reset(&watchers, &settings) {
-                        timer::reset()
-                    } else if split(&watchers, &settings) {
-                        timer::split()
+                } else {
+                    // Nothing can be read even though the emulator updated, so its RAM has most
+                    // likely moved (e.g. after loading a save state). Re-attach to find it again
+                    // instead of acting on values that are no longer being refreshed.
+                    failed_reads += 1;
+                    count_unreadable(&mut unread_ticks);
+                    if failed_reads == READ_FAILURE_LIMIT {
+                        asr::print_message("Game memory can't be read, re-attaching...");
+                        break;
                     }
+                    next_tick().await;
+                    continue;
                 }
 
-                if timer::state() == TimerState::NotRunning && start(&watchers, &settings) {
-                    timer::start();
-                    timer::pause_game_time();
+                if timer_state == TimerState::Running || timer_state == TimerState::Paused {
+                    pause_for_loads(&mut watchers, &settings);
 
-                    if let Some(is_loading) = is_loading(&watchers, &settings) {
-                        if is_loading {
-                            timer::pause_game_time()
+                    if let Some(game_time) = game_time(&watchers, &settings) {
+                        if !settings.practice_mode {
+                            timer::set_game_time(game_time);
+                        }
+                        watchers.run.last_game_time = Some(game_time);
+                        watchers.run.last_game_time_settings = Some(game_time_settings(&settings));
+                    }
+
+                    if reset_held(&mut watchers, &settings) {
+                        if settings.practice_mode {
+                            asr::print_message("Practice: reset");
+                            practicing = false;
                         } else {
-                            timer::resume_game_time()
+                            timer::reset()
+                        }
+                    } else {
+                        if settings.auto_undo_split {
+                            undo_rewound_splits(&mut watchers.run, settings.practice_mode);
                         }
+                        // LiveSplit ignores splits while the timer is paused, so the queue is
+                        // only worked through while it's running, keeping the split count and
+                        // times in step with the timer
+                        if timer_state == TimerState::Running
+                            && next_split(&mut watchers, &settings)
+                        {
+                            if settings.practice_mode {
+                                print_message(format_args!(
+                                    "Practice: split {}",
+                                    watchers.run.splits + 1
+                                ));
+                            } else {
+                                timer::split();
+                            }
+                            if let (Some(igt), Some(time)) =
+                                (&watchers.igt.pair, split_time(&watchers, &settings))
+                            {
+                                record_split_time(&mut watchers.run, igt.current, time);
+                            }
+                            watchers.run.splits += 1;
+                        }
+                    }
+                }
+
+                // The timer state can lag a tick behind timer::start(), so once started, the
+                // start condition has to go false again before it's allowed to start anew.
+                // Practice mode starts over on every start, so each attempt can be followed.
+                let should_start = start(&watchers, &settings, start_map);
+                if !should_start {
+                    start_latched = false;
+                }
+
+                if !start_latched
+                    && (armed || !settings.manual_arm)
+                    && (settings.practice_mode || timer::state() == TimerState::NotRunning)
+                    && should_start
+                {
+                    start_latched = true;
+                    armed = false;
+                    if settings.practice_mode {
+                        asr::print_message("Practice: start");
+                        practicing = true;
+                    } else {
+                        timer::start();
+                        auto_started = true;
+                        // See game_time(): LiveSplit's own game time clock never runs
+                        timer::pause_game_time();
+                    }
+                    begin_run(&mut watchers, &settings);
+                }
+            } else {
+                // The emulator is still open but its memory can't be read (e.g. paused or
+                // mid-reload). Once this has gone on for a while, drop the stale values so
+                // nothing keeps splitting or timing off a frozen snapshot.
+                failed_updates = failed_updates.saturating_add(1);
+                count_unreadable(&mut unread_ticks);
+                if failed_updates == STALE_UPDATE_LIMIT {
+                    timer::set_variable("autosplitter_status", "Stale");
+                    clear_watchers(&mut watchers);
+
+                    if settings.pause_on_stale
+                        && !settings.practice_mode
+                        && timer::state() == TimerState::Running
+                    {
+                        set_game_time_paused(&mut watchers, true);
                     }
                 }
             }
@@ -86,8 +300,158 @@ async fn main() {
     }
 }
 
+// Practice mode keeps the timer out of it and follows the run as if it had been started
+fn timer_state(settings: &Settings, practicing: bool) -> TimerState {
+    match (settings.practice_mode, practicing) {
+        (false, _) => timer::state(),
+        (true, true) => TimerState::Running,
+        (true, false) => TimerState::NotRunning,
+    }
+}
+
+// Number of ticks to wait between attempts to attach to an emulator
+const ATTACH_RETRY_TICKS: u32 = 30;
+
+// Number of consecutive failed emulator updates before the watched values are considered stale
+const STALE_UPDATE_LIMIT: u32 = 120;
+
+// Number of ticks without being able to read the game before warning about the emulator
+const UNSUPPORTED_EMULATOR_TICKS: u32 = 600;
+
+// Number of consecutive updates where nothing could be read before re-attaching
+const READ_FAILURE_LIMIT: u32 = 60;
+
+// Number of updates the IGT counter has to stand still before it counts as a load. The
+// counter only ticks at 30fps, so a few updates without a change are normal.
+const LOAD_FREEZE_UPDATES: u32 = 15;
+
+// Longest the IGT is held through zero reads on the same map, counted in updates, before the zero
+// is taken as the title screen rather than a menu
+const ZERO_IGT_HOLD_UPDATES: u32 = 240;
+
+// Number of splits per run whose game time is kept and published
+const SPLIT_TIMES: usize = 32;
+
+// Most item IDs the ignored_items setting can hold
+const IGNORED_ITEMS: usize = 8;
+
+// Item ID of an empty inventory slot. Nothing split on may share it, see items.rs.
+pub(crate) const EMPTY_SLOT: u16 = 0;
+
+// Number of updates the ending value and the ending map change may be apart and still count as
+// one ending
+const ENDING_WINDOW: u8 = 60;
+
+// Number of updates after a rewind that the inventory changes may still be the loaded save's
+const REWIND_WINDOW: u8 = 4;
+
+// Counts ticks where nothing could be read from the attached emulator. If that goes on for a
+// few seconds the emulator (or its version) most likely isn't one whose memory layout is known.
+fn count_unreadable(unread_ticks: &mut u32) {
+    *unread_ticks = unread_ticks.saturating_add(1);
+    if *unread_ticks == UNSUPPORTED_EMULATOR_TICKS {
+        print_message(format_args!(
+            "{} was found but the game can't be read from it. \
+             This emulator or version may not be supported, try a different one.",
+            attached_emulator()
+        ));
+        timer::set_variable("emulator_warning", "Emulator not supported");
+    }
+}
+
+// The processes asr's PS1 support attaches to, in the order it looks for them
+const EMULATOR_PROCESSES: [&str; 7] = [
+    "ePSXe.exe",
+    "psxfin.exe",
+    "duckstation-qt-x64-ReleaseLTCG.exe",
+    "duckstation-nogui-x64-ReleaseLTCG.exe",
+    "retroarch.exe",
+    "pcsx-redux.main",
+    "XEBRA.EXE",
+];
+
+// The emulator handle doesn't say which process it attached to, so this looks for the first of
+// them that's running, the same way attaching does
+fn attached_emulator() -> &'static str {
+    EMULATOR_PROCESSES
+        .into_iter()
+        .find(|name| asr::Process::attach(name).is_some())
+        .unwrap_or("An unknown emulator")
+}
+
+// Keeps trying to attach to a supported emulator, waiting a few ticks between attempts so
+// nothing is hammered while no emulator is running
+async fn attach() -> Emulator {
+    let mut logged = false;
+    loop {
+        if let Some(emulator) = Emulator::attach() {
+            return emulator;
+        }
+
+        if !logged {
+            asr::print_message("Waiting for emulator...");
+            logged = true;
+        }
+
+        for _ in 0..ATTACH_RETRY_TICKS {
+            next_tick().await;
+        }
+    }
+}
+
 #[derive(Gui)]
 struct Settings {
+    /// Route preset (anything but Custom overrides the split settings below)
+    preset: Preset,
+
+    #[default = true]
+    /// ---------- General Below ----------
+    _general: bool,
+
+    #[default = true]
+    /// Pause game time while the game can't be read
+    pause_on_stale: bool,
+
+    /// Timing method
+    timing_mode: TimingMode,
+
+    /// Longest load before game time is resumed anyway (real time without loads)
+    load_timeout: LoadTimeout,
+
+    /// How long the reset condition has to hold before the timer is reset
+    reset_delay: ResetDelay,
+
+    #[default = false]
+    /// Pause game time while HP is zero (practice)
+    pause_on_death: bool,
+
+    #[default = false]
+    /// Pause game time while a menu is open (real time with loads)
+    pause_on_menu: bool,
+
+    #[default = false]
+    /// Ignore single IGT reads that jump ahead by more than a second
+    smooth_igt: bool,
+
+    #[default = false]
+    /// Round game time down to whole seconds, like the in-game clock shows it
+    whole_seconds: bool,
+
+    /// Updates per second (lower uses less CPU)
+    update_rate: UpdateRate,
+
+    #[default = false]
+    /// Practice mode (log starts and splits instead of controlling the timer)
+    practice_mode: bool,
+
+    #[default = false]
+    /// Undo splits when a save or save state from before them is loaded (practice)
+    auto_undo_split: bool,
+
+    #[default = false]
+    /// Log a snapshot of every watched value (for bug reports)
+    dump: bool,
+
     #[default = true]
     /// ---------- Start Conditions Below ----------
     _condit: bool,
@@ -96,6 +460,17 @@ struct Settings {
     /// START --> Enable auto start
     start: bool,
 
+    /// START --> Start trigger
+    start_mode: StartMode,
+
+    #[default = false]
+    /// START --> Manual arm (only auto start once the timer has been reset by hand)
+    manual_arm: bool,
+
+    #[default = false]
+    /// START --> New Game+ (count from the IGT carried over at the start instead of zero)
+    new_game_plus: bool,
+
     #[default = true]
     /// ---------- End Split Below ----------
     _ending: bool,
@@ -104,6 +479,17 @@ struct Settings {
     /// Splits on either Good End or Bad End
     end: bool,
 
+    /// Maps that count as the finish
+    end_maps: EndMaps,
+
+    #[default = false]
+    /// Bad End - Split on entering its map even without the ending value (timed out ending)
+    bad_end_map_only: bool,
+
+    #[default = false]
+    /// Split again when the ending map is left for the credits
+    credits: bool,
+
     #[default = true]
     /// ---------- Door Splits Below ----------
     _doors: bool,
@@ -112,169 +498,635 @@ struct Settings {
     /// Door splits - Will split on every room
     door_split: bool,
 
+    /// Door splits - Minimum game time between two door splits
+    door_split_interval: DoorSplitInterval,
+
+    #[default = false]
+    /// Door splits - Forward only (skip doors into a lower map ID, rough backtracking filter)
+    door_split_forward: bool,
+
+    /// Unique rooms - Split every time this many different rooms have been entered
+    unique_rooms: UniqueRooms,
+
+    #[default = true]
+    /// ---------- Disc Swap Split Below ----------
+    _disc_swap: bool,
+
+    #[default = false]
+    /// Disc swap - Split when Disc 2 is inserted
+    split_on_disc_swap: bool,
+
+    #[default = true]
+    /// ---------- Health Split Below ----------
+    _health: bool,
+
+    /// Low HP - Split when HP drops below
+    low_hp: LowHp,
+
+    #[cfg(not(feature = "doors-only"))]
     #[default = true]
     /// ---------- Item Splits Below ----------
     _items: bool,
 
+    #[cfg(not(feature = "doors-only"))]
+    /// Split on each key item below when it is
+    key_split: items::KeySplit,
+
+    #[cfg(not(feature = "doors-only"))]
     #[default = false]
     /// Keno Ticket
     keno: bool,
 
+    #[cfg(not(feature = "doors-only"))]
     #[default = false]
     /// VIP Suzie Card
     susie: bool,
 
+    #[cfg(not(feature = "doors-only"))]
     #[default = false]
     /// VIP Nancy Card
     nancy: bool,
 
+    #[cfg(not(feature = "doors-only"))]
     #[default = false]
     /// VIP Cheryl Card
     cheryl: bool,
 
+    #[cfg(not(feature = "doors-only"))]
     #[default = false]
     /// Show Stage Key
     stagekey: bool,
 
+    #[cfg(not(feature = "doors-only"))]
     #[default = false]
     /// VIP Leagan Card
     leagan: bool,
 
+    #[cfg(not(feature = "doors-only"))]
     #[default = false]
     /// Attraction Key
     attract: bool,
 
+    #[cfg(not(feature = "doors-only"))]
     #[default = false]
     /// Museum Key
     museum: bool,
 
+    #[cfg(not(feature = "doors-only"))]
     #[default = false]
     /// Desert Moon Control Room Key
     moon: bool,
 
+    #[cfg(not(feature = "doors-only"))]
     #[default = false]
     /// Key to "Evil House"
     evil: bool,
 
+    #[cfg(not(feature = "doors-only"))]
     #[default = false]
     /// The Spear Key
     spear: bool,
 
+    #[cfg(not(feature = "doors-only"))]
     #[default = false]
     /// Card Disk C
     cardc: bool,
 
+    #[cfg(not(feature = "doors-only"))]
     #[default = false]
     /// Card Disk D
     cardd: bool,
 
+    #[cfg(not(feature = "doors-only"))]
     #[default = false]
     /// VIP Sydney Card
     sydney: bool,
 
+    #[cfg(not(feature = "doors-only"))]
     #[default = false]
     /// No.9 Playing Card
     card9: bool,
 
+    #[cfg(not(feature = "doors-only"))]
     #[default = false]
     /// Blue Clock Hand
     bluehand: bool,
 
+    #[cfg(not(feature = "doors-only"))]
     #[default = false]
     /// Red Clock Hand
     redhand: bool,
 
+    #[cfg(not(feature = "doors-only"))]
     #[default = false]
     /// Panel No.1
     panel1: bool,
 
+    #[cfg(not(feature = "doors-only"))]
     #[default = false]
     /// Event Room Key
     event: bool,
 
+    #[cfg(not(feature = "doors-only"))]
     #[default = false]
     /// Panel No.2
     panel2: bool,
 
+    #[cfg(not(feature = "doors-only"))]
     #[default = false]
     /// Panel No.4
     panel4: bool,
 
+    #[cfg(not(feature = "doors-only"))]
     #[default = false]
     /// Panel No.6
     panel6: bool,
 
+    #[cfg(not(feature = "doors-only"))]
     #[default = false]
     /// Y-Shaped Panel Key
     ykey: bool,
 
+    #[cfg(not(feature = "doors-only"))]
     #[default = false]
     /// Key to Passageway D-4
     d4: bool,
 
+    #[cfg(not(feature = "doors-only"))]
     #[default = false]
     /// Key to Shipping Area Parking Lot
     lot: bool,
 
+    #[cfg(not(feature = "doors-only"))]
     #[default = false]
     /// Key to Campground Vehicle
     camp: bool,
 
+    #[cfg(not(feature = "doors-only"))]
     #[default = false]
     /// Key to Small Storage Room
     small: bool,
 
+    #[cfg(not(feature = "doors-only"))]
     #[default = false]
     /// Forklift Key
     fork: bool,
 
+    #[cfg(not(feature = "doors-only"))]
     #[default = false]
     /// Log House Key
     log: bool,
 
+    #[cfg(not(feature = "doors-only"))]
     #[default = false]
     /// Key to the "Guesthouse"
     guest: bool,
 
+    #[cfg(not(feature = "doors-only"))]
     #[default = false]
     /// Shower Room Key
     shower: bool,
 
+    #[cfg(not(feature = "doors-only"))]
     #[default = false]
     /// Key to Chainsaw Shelf
     shelf: bool,
 
+    #[cfg(not(feature = "doors-only"))]
     #[default = false]
     /// Bourbon
     bourbon: bool,
 
+    #[cfg(not(feature = "doors-only"))]
     #[default = false]
     /// Marlintown Gate Key
     marlin: bool,
 
+    #[cfg(not(feature = "doors-only"))]
     #[default = false]
     /// Chainsaw
     chain: bool,
 
+    #[cfg(not(feature = "doors-only"))]
     #[default = false]
     /// Observation Room Key
     observ: bool,
 
+    #[cfg(not(feature = "doors-only"))]
     #[default = false]
     /// Sterilization Passageway Key
     sterile: bool,
 
+    #[cfg(not(feature = "doors-only"))]
     #[default = false]
     /// M82A1
     m8: bool,
 
+    #[cfg(not(feature = "doors-only"))]
     #[default = false]
     /// Code - SIN Key
     sin: bool,
 
+    #[cfg(not(feature = "doors-only"))]
     #[default = false]
     /// Fuse
     fuse: bool,
+
+    #[cfg(not(feature = "doors-only"))]
+    #[default = true]
+    /// ---------- Item Set Splits Below ----------
+    _sets: bool,
+
+    #[cfg(not(feature = "doors-only"))]
+    #[default = false]
+    /// All 5 VIP Cards (Susie, Nancy, Cheryl, Leagan, Sydney)
+    vip_cards: bool,
+
+    #[cfg(not(feature = "doors-only"))]
+    #[default = false]
+    /// All 4 Panels (No.1, No.2, No.4, No.6)
+    panels: bool,
+
+    #[cfg(not(feature = "doors-only"))]
+    #[default = false]
+    /// Both Clock Hands (Blue, Red)
+    clock_hands: bool,
+
+    #[cfg(not(feature = "doors-only"))]
+    #[default = false]
+    /// Lab access - Both lab keys, or the next room after using the Sterilization key
+    lab_access: bool,
+
+    #[cfg(not(feature = "doors-only"))]
+    /// Key items - Split once this many different ones have been collected (100%)
+    key_item_target: items::KeyItemTarget,
+
+    #[cfg(not(feature = "doors-only"))]
+    #[default = true]
+    /// ---------- Room Entry Splits Below ----------
+    _rooms: bool,
+
+    #[cfg(not(feature = "doors-only"))]
+    #[default = false]
+    /// Desert Moon Control Room - Next room after using its key
+    moon_room: bool,
+
+    #[cfg(not(feature = "doors-only"))]
+    #[default = false]
+    /// Marlintown - Next room after using the gate key
+    marlin_gate: bool,
+
+    #[cfg(not(feature = "doors-only"))]
+    #[default = false]
+    /// Code - SIN Key - Using it on the puzzle
+    sin_used: bool,
+
+    #[cfg(not(feature = "doors-only"))]
+    #[default = false]
+    /// Y-Shaped Panel Key - Using it once every panel has been collected
+    y_panel: bool,
+
+    #[cfg(not(feature = "doors-only"))]
+    #[default = false]
+    /// Fuse - Entering the next room once the power is back on
+    fuse_power: bool,
+
+    #[cfg(not(feature = "doors-only"))]
+    #[default = false]
+    /// Evil House - Next room after using its key
+    evil_house: bool,
+
+    #[cfg(not(feature = "doors-only"))]
+    #[default = false]
+    /// Show Stage - Next room after using its key
+    show_stage: bool,
+
+    #[cfg(not(feature = "doors-only"))]
+    #[default = false]
+    /// Attraction - Next room after using its key
+    attraction_area: bool,
+
+    #[cfg(not(feature = "doors-only"))]
+    #[default = false]
+    /// Museum - Next room after using its key
+    museum_area: bool,
+
+    #[cfg(not(feature = "doors-only"))]
+    #[default = false]
+    /// Spear Key - Next room after using it
+    spear_area: bool,
+
+    #[cfg(not(feature = "doors-only"))]
+    #[default = false]
+    /// Event Room - Next room after using its key
+    event_room: bool,
+
+    #[cfg(not(feature = "doors-only"))]
+    #[default = false]
+    /// Campground - Next room after using the vehicle key
+    campground: bool,
+
+    #[cfg(not(feature = "doors-only"))]
+    #[default = false]
+    /// Forklift - Next room after using its key
+    forklift: bool,
+
+    #[cfg(not(feature = "doors-only"))]
+    #[default = false]
+    /// Guesthouse - Next room after using its key
+    guesthouse: bool,
+
+    #[cfg(not(feature = "doors-only"))]
+    #[default = false]
+    /// Attraction, Museum and Desert Moon - Next room after using each of their keys
+    park_areas: bool,
+
+    #[cfg(not(feature = "doors-only"))]
+    #[default = true]
+    /// ---------- Consumable Splits Below ----------
+    _consumables: bool,
+
+    #[cfg(not(feature = "doors-only"))]
+    #[default = false]
+    /// Bourbon - Split on every bottle collected
+    bourbon_each: bool,
+
+    #[cfg(not(feature = "doors-only"))]
+    #[default = true]
+    /// ---------- Weapon Splits Below ----------
+    _weapons: bool,
+
+    #[cfg(not(feature = "doors-only"))]
+    #[default = false]
+    /// Chainsaw - Split when equipped
+    chain_equipped: bool,
+
+    #[cfg(not(feature = "doors-only"))]
+    /// M82A1 - Split when its ammo first reaches (unverified, reads the slot's quantity as ammo)
+    m8_ammo: items::AmmoTarget,
+}
+
+#[derive(Gui, Clone, Copy, PartialEq, Eq)]
+enum Preset {
+    /// Custom
+    #[default]
+    Custom,
+    /// Any% - The route's key items and the ending
+    AnyPercent,
+    /// Door splits - Every room and the ending
+    Doors,
+}
+
+impl Settings {
+    // Fills in the split settings for the chosen preset. This only changes the values read on
+    // this tick; the next update() reads the checkboxes again, so picking Custom hands control
+    // straight back to them.
+    fn apply_preset(&mut self) {
+        match self.preset {
+            Preset::Custom => return,
+            Preset::AnyPercent => {
+                self.door_split = false;
+                items::preset(self, true);
+            }
+            Preset::Doors => {
+                self.door_split = true;
+                items::preset(self, false);
+            }
+        }
+        self.door_split_forward = false;
+        self.unique_rooms = UniqueRooms::Off;
+        self.end = true;
+        self.credits = false;
+        self.split_on_disc_swap = false;
+        self.low_hp = LowHp::Off;
+    }
+
+    fn conflict(&self) -> Option<&'static str> {
+        CONFLICTS
+            .iter()
+            .find(|(conflicts, _)| conflicts(self))
+            .map(|&(_, message)| message)
+    }
+}
+
+// Combinations of settings that can't both take effect, with a warning that says which one wins.
+// The precedence is how the rest of the code already behaves, this only makes it visible.
+type Conflicts = fn(&Settings) -> bool;
+
+const CONFLICTS: [(Conflicts, &str); 2] = [
+    (
+        |settings| settings.pause_on_death && settings.timing_mode != TimingMode::Igt,
+        "Pause on death only works with in-game time and is ignored with the other timing modes \
+         (raw IGT included)",
+    ),
+    (
+        |settings| settings.pause_on_menu && settings.timing_mode != TimingMode::Rta,
+        "Pause on menu only applies to real time with loads, the other timing modes already \
+         leave out the time the IGT stands still",
+    ),
+];
+
+#[derive(Gui, Clone, Copy, PartialEq, Eq)]
+enum TimingMode {
+    /// In-game time
+    #[default]
+    Igt,
+    /// Real time without loads
+    RtaNoLoads,
+    /// Real time with loads
+    Rta,
+    /// Raw in-game time counter (goes back when a save is loaded)
+    RawIgt,
+}
+
+#[derive(Gui, Clone, Copy, PartialEq, Eq)]
+enum StartMode {
+    /// New game (IGT starts counting)
+    #[default]
+    NewGame,
+    /// Entering the next room (IL / segment practice from a save)
+    RoomEntry,
+    /// Gain of control (new game, once the opening map is left)
+    GainOfControl,
+    /// Entering a chosen room (the start_map entry, see the README)
+    ChosenRoom,
+}
+
+#[derive(Gui, Clone, Copy, PartialEq, Eq)]
+enum EndMaps {
+    /// Good End (123) or Bad End (110)
+    #[default]
+    Both,
+    /// Good End only (123)
+    GoodEnd,
+    /// Bad End only (110)
+    BadEnd,
+    /// Any map entered as the ending is reached
+    Any,
+}
+
+impl EndMaps {
+    fn contains(self, map_id: u16) -> bool {
+        match self {
+            Self::Both => map_id == 123 || map_id == 110,
+            Self::GoodEnd => map_id == 123,
+            Self::BadEnd => map_id == 110,
+            Self::Any => true,
+        }
+    }
+}
+
+#[derive(Gui, Clone, Copy, PartialEq, Eq)]
+enum DoorSplitInterval {
+    /// No minimum
+    #[default]
+    Off,
+    /// 0.5 seconds
+    HalfSecond,
+    /// 1 second
+    OneSecond,
+    /// 2 seconds
+    TwoSeconds,
+    /// 5 seconds
+    FiveSeconds,
+}
+
+impl DoorSplitInterval {
+    fn duration(self) -> Duration {
+        match self {
+            Self::Off => Duration::ZERO,
+            Self::HalfSecond => Duration::milliseconds(500),
+            Self::OneSecond => Duration::seconds(1),
+            Self::TwoSeconds => Duration::seconds(2),
+            Self::FiveSeconds => Duration::seconds(5),
+        }
+    }
+}
+
+#[derive(Gui, Clone, Copy, PartialEq, Eq)]
+enum LoadTimeout {
+    /// Never
+    Off,
+    /// 30 seconds
+    ThirtySeconds,
+    /// 1 minute
+    #[default]
+    OneMinute,
+    /// 2 minutes
+    TwoMinutes,
+}
+
+impl LoadTimeout {
+    fn duration(self) -> Option<Duration> {
+        match self {
+            Self::Off => None,
+            Self::ThirtySeconds => Some(Duration::seconds(30)),
+            Self::OneMinute => Some(Duration::minutes(1)),
+            Self::TwoMinutes => Some(Duration::minutes(2)),
+        }
+    }
+}
+
+#[derive(Gui, Clone, Copy, PartialEq, Eq)]
+enum UniqueRooms {
+    /// Off
+    #[default]
+    Off,
+    /// Every 5 rooms
+    Five,
+    /// Every 10 rooms
+    Ten,
+    /// Every 25 rooms
+    TwentyFive,
+}
+
+impl UniqueRooms {
+    fn every(self) -> Option<u32> {
+        match self {
+            Self::Off => None,
+            Self::Five => Some(5),
+            Self::Ten => Some(10),
+            Self::TwentyFive => Some(25),
+        }
+    }
+}
+
+#[derive(Gui, Clone, Copy, PartialEq, Eq)]
+enum ResetDelay {
+    /// Reset right away
+    #[default]
+    Off,
+    /// Half a second
+    HalfSecond,
+    /// 1 second
+    OneSecond,
+    /// 3 seconds
+    ThreeSeconds,
+}
+
+impl ResetDelay {
+    fn duration(self) -> Duration {
+        match self {
+            Self::Off => Duration::ZERO,
+            Self::HalfSecond => Duration::milliseconds(500),
+            Self::OneSecond => Duration::seconds(1),
+            Self::ThreeSeconds => Duration::seconds(3),
+        }
+    }
+}
+
+#[derive(Gui, Clone, Copy, PartialEq, Eq)]
+enum LowHp {
+    /// Off
+    #[default]
+    Off,
+    /// 10 HP
+    Ten,
+    /// 25 HP
+    TwentyFive,
+    /// 50 HP
+    Fifty,
+}
+
+impl LowHp {
+    fn threshold(self) -> Option<u16> {
+        match self {
+            Self::Off => None,
+            Self::Ten => Some(10),
+            Self::TwentyFive => Some(25),
+            Self::Fifty => Some(50),
+        }
+    }
+}
+
+// Everything counted in updates (load detection, the ending window, the stale and re-attach
+// limits) stretches out in time at the lower rates. The IGT only ticks 30 times a second, so even
+// the lowest rate sees every frame of it, and the rewind and wrap checks go by IGT, not updates.
+#[derive(Gui, Clone, Copy, PartialEq, Eq)]
+enum UpdateRate {
+    /// 120 (default)
+    #[default]
+    Full,
+    /// 60
+    Half,
+    /// 30
+    Quarter,
+}
+
+impl UpdateRate {
+    fn ticks_per_second(self) -> f64 {
+        match self {
+            Self::Full => 120.0,
+            Self::Half => 60.0,
+            Self::Quarter => 30.0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Disc {
+    One,
+    Two,
 }
 
 // Defines the watcher type of
@@ -284,9 +1136,89 @@ struct Watchers {
     igt: Watcher<Duration>,
     map_id: Watcher<u16>,
     inventory: Watcher<[u16; 12]>,
+    quantities: Watcher<[u16; 12]>,
+    states: Watcher<[u16; 12]>,
     ending: Watcher<u16>,
+    map_change_age: Option<u8>,
+    game_confirmed: bool,
+    left_game: bool,
+    gamecode: Option<[u8; 11]>,
+    unknown_gamecode: Option<[u8; 11]>,
+    new_game_map: Option<u16>,
+    igt_frozen_for: u32,
+    // Updates the IGT has read zero for while being held, see update_loop()
+    zero_igt_for: u32,
+    frozen_on_map: Option<u16>,
+    last_igt_read: Duration,
+    loading_since: Option<Instant>,
+    load_timed_out: bool,
+    last_map_id: Option<u16>,
+    validated: bool,
+    disc: Option<Disc>,
+    last_items: [u16; 12],
+    last_quantities: [u16; 12],
+    last_states: [u16; 12],
+    run: RunState,
+}
+
+// Everything accounted for the current attempt, as opposed to the values read from memory. All
+// of it starts over from the defaults whenever the timer isn't running, see reset_run_state().
+#[derive(Default)]
+struct RunState {
     accumulated_igt: Duration,
     buffer_igt: Duration,
+    rebase_igt: bool,
+    // Updates since the ending value changed to 0xFFFF, see ending()
+    ending_age: Option<u8>,
+    // Whether the ending value was seen changing to 0xFFFF during this attempt. The value can
+    // still be set from the previous attempt, which mustn't count.
+    ending_reached: bool,
+    #[cfg(not(feature = "doors-only"))]
+    items: items::ItemProgress,
+    // What the in-game counter was rewound to this update, if a save was loaded
+    rewound_to: Option<Duration>,
+    // Updates since the last rewind, while the inventory may still be catching up with it
+    rewind_age: Option<u8>,
+    disc_swapped: Watcher<bool>,
+    died_at: Option<Duration>,
+    death_igt: Duration,
+    // Same for while the LiveSplit timer is paused: the run IGT it was paused at, and the IGT
+    // that passed during earlier pauses
+    timer_paused_at: Option<Duration>,
+    timer_paused_igt: Duration,
+    splits: u32,
+    queued_splits: u32,
+    // One bit per queued split, front of the queue in the lowest bit, for the ones that are door
+    // splits
+    queued_doors: u32,
+    split_times: [Duration; SPLIT_TIMES],
+    // The in-game counter's own value at each split, for undoing the splits a loaded save went
+    // back past
+    split_igt: [Duration; SPLIT_TIMES],
+    last_door_split: Option<Duration>,
+    last_game_time: Option<Duration>,
+    // What game_time_settings() were when last_game_time was taken
+    last_game_time_settings: Option<(TimingMode, bool, bool)>,
+    // One bit per map ID seen this run, for the unique rooms split. Map IDs past the end of the
+    // set aren't counted.
+    visited_rooms: [u64; 16],
+    unique_rooms: Watcher<u32>,
+    // Whether game time was last paused or resumed, so LiveSplit is only told when that changes.
+    // None until the first call of the run, as LiveSplit's state isn't known before then.
+    game_time_paused: Option<bool>,
+    // When the reset condition started holding, for the reset delay
+    reset_since: Option<Instant>,
+    // IGT freezes long enough to count as a load this run, and the real time they took, for
+    // the stats logged at the finish. The time is counted from when a freeze starts counting
+    // as a load, so the first LOAD_FREEZE_UPDATES of each one are left out.
+    loads: u32,
+    load_time: Duration,
+    load_started: Option<Instant>,
+    // Shortest, longest and latest of those loads, published to check the freezes taken as
+    // loads have sensible lengths
+    shortest_load: Option<Duration>,
+    longest_load: Option<Duration>,
+    last_load: Option<Duration>,
 }
 
 struct Offsets {
@@ -312,134 +1244,1188 @@ impl Offsets {
     }
 }
 
-fn update_loop(game: &Emulator, offsets: &Offsets, watchers: &mut Watchers) {
-    match &game
-        .read::<[u8; 11]>(offsets.gamecode_ntsc)
-        .unwrap_or_default()
-    {
-        b"SLUS_008.98" | b"SLUS_011.99" => {
+// The reads update_loop() makes, so it can run against a RAM dump in the tests as well as against
+// the emulator
+trait Memory {
+    fn read_u16(&self, address: u32) -> Option<u16>;
+    fn read_u32(&self, address: u32) -> Option<u32>;
+    fn read_gamecode(&self, address: u32) -> Option<[u8; 11]>;
+    fn read_inventory(&self, address: u32) -> Option<[[u16; 3]; 12]>;
+}
+
+impl Memory for Emulator {
+    fn read_u16(&self, address: u32) -> Option<u16> {
+        self.read(address).ok()
+    }
+
+    fn read_u32(&self, address: u32) -> Option<u32> {
+        self.read(address).ok()
+    }
+
+    fn read_gamecode(&self, address: u32) -> Option<[u8; 11]> {
+        self.read(address).ok()
+    }
+
+    fn read_inventory(&self, address: u32) -> Option<[[u16; 3]; 12]> {
+        self.read(address).ok()
+    }
+}
+
+// Returns false, leaving the watchers untouched, if not even the game code could be read
+fn update_loop(
+    game: &impl Memory,
+    offsets: &Offsets,
+    watchers: &mut Watchers,
+    settings: &Settings,
+    ignored_items: &[u16; IGNORED_ITEMS],
+    timer_state: TimerState,
+) -> bool {
+    let Some(raw_gamecode) = game.read_gamecode(offsets.gamecode_ntsc) else {
+        return false;
+    };
+
+    watchers.gamecode = Some(raw_gamecode);
+    let gamecode = normalize_gamecode(raw_gamecode);
+    match &gamecode {
+        b"SLUS00898" | b"SLUS01199" if watchers.validated || layout_plausible(game, offsets) => {
             // The gamecodes provided above ensure you are running the correct game
-            watchers.hp.update(game.read::<u16>(offsets.hp).ok());
-            watchers.igt.update_infallible(frame_count::<30>(
-                game.read::<u32>(offsets.igt).unwrap_or_default() as _,
-            ));
-            watchers
-                .map_id
-                .update(game.read::<u16>(offsets.map_id).ok());
-            watchers.inventory.update_infallible(
-                game.read::<[[u16; 3]; 12]>(offsets.item_1)
-                    .unwrap_or_default()
-                    .map(|[item, _, _]| item),
+            watchers.validated = true;
+            timer::set_variable("autosplitter_status", "Attached");
+            watchers.game_confirmed = true;
+
+            // The disc is remembered through the unreadable stretch while discs are being
+            // swapped, so the swap is only seen once Disc 2 is actually running
+            let disc = if gamecode == *b"SLUS01199" {
+                Disc::Two
+            } else {
+                Disc::One
+            };
+            let swapped = watchers
+                .run
+                .disc_swapped
+                .pair
+                .is_some_and(|pair| pair.current)
+                || (watchers.disc == Some(Disc::One) && disc == Disc::Two);
+            watchers.run.disc_swapped.update_infallible(swapped);
+            watchers.disc = Some(disc);
+            timer::set_variable(
+                "current_disc",
+                match disc {
+                    Disc::One => "1",
+                    Disc::Two => "2",
+                },
             );
-            watchers
-                .ending
-                .update(game.read::<u16>(offsets.ending).ok());
+
+            watchers.hp.update(game.read_u16(offsets.hp));
+            // Transitions can pass through a placeholder map for a frame, so a new map is
+            // only taken once it has been read twice in a row
+            let map_id = game.read_u16(offsets.map_id);
+            let previous_map_id = core::mem::replace(&mut watchers.last_map_id, map_id);
+            let settled_map_id = match map_id {
+                Some(_) if map_id != previous_map_id => {
+                    watchers.map_id.pair.map(|pair| pair.current).or(map_id)
+                }
+                _ => map_id,
+            };
+            watchers.map_id.update(settled_map_id);
+            // The IGT reads zero in some menus. A zero while still on the same map as the
+            // previous update is taken as one of those rather than a new game, which only
+            // starts after a map change, so the last value is held instead. Quitting to the
+            // title screen can keep the map too, so the hold ends after ZERO_IGT_HOLD_UPDATES
+            // and the zero comes through for the next new game to start from. A failed read
+            // holds the last value as well.
+            let held_igt = watchers.igt.pair.map(|pair| pair.current);
+            let in_gameplay = watchers
+                .map_id
+                .pair
+                .is_some_and(|pair| pair.unchanged() && pair.current != 0);
+            let igt = match game.read_u32(offsets.igt) {
+                Some(0)
+                    if in_gameplay
+                        && held_igt.is_some_and(|igt| igt != Duration::ZERO)
+                        && watchers.zero_igt_for < ZERO_IGT_HOLD_UPDATES =>
+                {
+                    watchers.zero_igt_for += 1;
+                    held_igt
+                }
+                Some(frames) => {
+                    watchers.zero_igt_for = 0;
+                    Some(frame_count::<30>(frames as _))
+                }
+                None => held_igt,
+            };
+            // A jump of more than a second in one update is only taken once a second read
+            // agrees with it, so a single bad read doesn't show up in the timer or the
+            // rewind accounting. Loading a later save still comes through an update late.
+            let igt = match (igt, held_igt) {
+                (Some(read), Some(held))
+                    if settings.smooth_igt
+                        && read - held > Duration::SECOND
+                        && (read - watchers.last_igt_read).abs() > Duration::SECOND =>
+                {
+                    watchers.last_igt_read = read;
+                    Some(held)
+                }
+                (igt, _) => {
+                    watchers.last_igt_read = igt.unwrap_or_default();
+                    igt
+                }
+            };
+            watchers.igt.update_infallible(igt.unwrap_or_default());
+            // Each inventory slot is an [item, quantity, state] triple. A failed read holds
+            // the inventory where it was instead of emptying it, which would look like every
+            // item being dropped and picked back up.
+            if let Some(inventory) = game.read_inventory(offsets.item_1) {
+                // During room transitions the slots can briefly hold garbage, so a slot only
+                // takes a new value once the same value has been read twice in a row
+                let items = settle(
+                    &mut watchers.last_items,
+                    inventory.map(|[item, _, _]| {
+                        if ignored_items.contains(&item) {
+                            EMPTY_SLOT
+                        } else {
+                            item
+                        }
+                    }),
+                    &watchers.inventory,
+                );
+                let quantities = settle(
+                    &mut watchers.last_quantities,
+                    inventory.map(|[_, quantity, _]| quantity),
+                    &watchers.quantities,
+                );
+                let states = settle(
+                    &mut watchers.last_states,
+                    inventory.map(|[_, _, state]| state),
+                    &watchers.states,
+                );
+                // Only logged, as there may well be items outside the range seen so far
+                if watchers
+                    .inventory
+                    .pair
+                    .is_none_or(|pair| pair.current != items)
+                {
+                    if let Some(item) = items.iter().find(|&&item| !known_item(item)) {
+                        print_message(format_args!(
+                            "Inventory settled on an unknown item ID {item}: {items:?}"
+                        ));
+                    }
+                }
+                watchers.inventory.update_infallible(items);
+                watchers.quantities.update_infallible(quantities);
+                watchers.states.update_infallible(states);
+            } else {
+                hold(&mut watchers.inventory);
+                hold(&mut watchers.quantities);
+                hold(&mut watchers.states);
+            }
+            watchers.ending.update(game.read_u16(offsets.ending));
         }
         _ => {
-            // If the emulator is loading the wrong game, the watchers will update to their default state
-            watchers.hp.update_infallible(u16::default());
-            watchers.igt.update_infallible(Duration::default());
-            watchers.map_id.update_infallible(u16::default());
-            watchers.inventory.update_infallible([u16::default(); 12]);
-            watchers.ending.update_infallible(u16::default());
+            // If the emulator is loading the wrong game (or a disc is being swapped), the watchers
+            // hold their last values, so nothing reads as having changed when the game is back
+            timer::set_variable("autosplitter_status", "Wrong Game");
+            timer::set_variable("current_disc", "Unknown");
+            // Logged once per code, so a new dump's variant of the game code can be added. A
+            // known code only ends up here when the memory layout check failed.
+            let known = matches!(&gamecode, b"SLUS00898" | b"SLUS01199");
+            if !known && watchers.unknown_gamecode != Some(raw_gamecode) {
+                watchers.unknown_gamecode = Some(raw_gamecode);
+                print_message(format_args!(
+                    "Unrecognised game code: {:02X?}",
+                    raw_gamecode
+                ));
+            }
+            watchers.left_game |= watchers.game_confirmed;
+            watchers.game_confirmed = false;
+            hold(&mut watchers.hp);
+            hold(&mut watchers.igt);
+            hold(&mut watchers.map_id);
+            hold(&mut watchers.inventory);
+            hold(&mut watchers.quantities);
+            hold(&mut watchers.states);
+            hold(&mut watchers.ending);
         }
     };
 
+    // Done before anything that looks at the inventory, so changes that come with a rewind can be
+    // told apart
+    watchers.run.rewound_to = None;
+    if let Some(igt) = &watchers.igt.pair {
+        // First IGT read after a re-attach, see carry_run_state()
+        if watchers.run.rebase_igt {
+            watchers.run.buffer_igt = igt.current;
+            watchers.run.rebase_igt = false;
+        }
+        if igt.old > igt.current {
+            // The frame counter is a u32. If it only moved forward a moment modulo 2^32 it
+            // wrapped around rather than being rewound, so keep counting across the wrap.
+            let wrap = frame_count::<30>(1 << 32);
+            if igt.current + wrap - igt.old < Duration::SECOND {
+                watchers.run.accumulated_igt += wrap;
+            } else {
+                watchers.run.accumulated_igt += igt.old - watchers.run.buffer_igt;
+                watchers.run.buffer_igt = igt.current;
+                // Going to zero is the counter being cleared on the title screen, nothing has
+                // been loaded yet
+                if igt.current != Duration::ZERO {
+                    watchers.run.rewound_to = Some(igt.current);
+                }
+            }
+        } else if igt.old == Duration::ZERO && igt.current - igt.old > Duration::SECOND {
+            // Coming off the title screen's zero with more than a new game's first frames is a
+            // save being loaded, or a menu the hold ran out on being closed. The time up to that
+            // value was banked as the counter went to zero, so count on from here.
+            watchers.run.buffer_igt = igt.current;
+            watchers.run.rewound_to = Some(igt.current);
+        }
+        debug_assert!(
+            watchers.run.buffer_igt <= igt.current + watchers.run.accumulated_igt,
+            "run IGT went negative: {:?} - {:?} + {:?}",
+            Clock(igt.current),
+            Clock(watchers.run.buffer_igt),
+            Clock(watchers.run.accumulated_igt)
+        );
+    }
+    // The inventory is settled an update after it's read, so whatever a loaded save took out of
+    // it only shows up on the updates after the rewind
+    watchers.run.rewind_age = event_age(
+        watchers.run.rewind_age,
+        watchers.run.rewound_to.is_some(),
+        REWIND_WINDOW,
+    );
+
+    // The ending value and the move to an ending map aren't always written on the same frame,
+    // so track how many updates ago each of them happened
+    watchers.run.ending_age = event_age(
+        watchers.run.ending_age,
+        watchers.ending.pair.is_some_and(|i| i.changed_to(&0xFFFF)),
+        ENDING_WINDOW,
+    );
+    watchers.run.ending_reached |= watchers.run.ending_age == Some(0);
+    watchers.map_change_age = event_age(
+        watchers.map_change_age,
+        watchers.map_id.pair.is_some_and(|i| i.changed()),
+        ENDING_WINDOW,
+    );
+
+    items::update(watchers);
+
+    // Count how long the IGT counter has been standing still, for RTA minus loads
+    if watchers.igt.pair.is_some_and(|igt| igt.unchanged()) {
+        watchers.igt_frozen_for = watchers.igt_frozen_for.saturating_add(1);
+        if watchers.igt_frozen_for == LOAD_FREEZE_UPDATES {
+            watchers.run.loads += 1;
+            watchers.run.load_started = Some(Instant::now());
+        }
+        if watchers.frozen_on_map.is_none() {
+            watchers.frozen_on_map = watchers.map_id.pair.map(|pair| pair.current);
+        }
+    } else {
+        if let Some(started) = watchers.run.load_started.take() {
+            let load = Instant::now() - started;
+            watchers.run.load_time += load;
+            watchers.run.last_load = Some(load);
+            watchers.run.shortest_load = Some(
+                watchers
+                    .run
+                    .shortest_load
+                    .map_or(load, |shortest| shortest.min(load)),
+            );
+            watchers.run.longest_load = Some(
+                watchers
+                    .run
+                    .longest_load
+                    .map_or(load, |longest| longest.max(load)),
+            );
+            publish_load_stats(&watchers.run);
+        }
+        watchers.igt_frozen_for = 0;
+        watchers.frozen_on_map = None;
+        watchers.loading_since = None;
+        watchers.load_timed_out = false;
+    }
+
+    // Remember which map a new game started on, so the start can wait until the player
+    // leaves the opening sequence. A new game is told apart from a loaded save the same way the
+    // new game start does it, see start().
+    if let (Some(igt), Some(map_id)) = (&watchers.igt.pair, &watchers.map_id.pair) {
+        if igt.current == Duration::ZERO {
+            watchers.new_game_map = None;
+        } else if igt.changed_from(&Duration::ZERO) && igt.current < Duration::SECOND {
+            watchers.new_game_map = Some(map_id.current);
+        }
+    }
+
+    if let Some(map_id) = watchers.map_id.pair.filter(|_| watchers.game_confirmed) {
+        let (word, bit) = (map_id.current as usize / 64, map_id.current % 64);
+        if let Some(word) = watchers.run.visited_rooms.get_mut(word) {
+            *word |= 1 << bit;
+        }
+    }
+    watchers.run.unique_rooms.update_infallible(
+        watchers
+            .run
+            .visited_rooms
+            .iter()
+            .map(|word| word.count_ones())
+            .sum(),
+    );
+
+    let igt = watchers.igt.pair.map(|igt| igt.current);
+    if let Some(previous) = sync_run_state(&mut watchers.run, timer_state, igt) {
+        clear_run_variables(&previous, &watchers.run);
+    }
+    set_variable("splits", format_args!("{}", watchers.run.splits));
+    if let Some(map_id) = &watchers.map_id.pair {
+        set_variable("room", format_args!("{}", Room(map_id.current)));
+    }
+    // Only 0xFFFF is understood so far, so every value is shown and logged to help map out
+    // what the others mean
+    if let Some(ending) = &watchers.ending.pair {
+        set_variable("ending", format_args!("{:04X}", ending.current));
+        if watchers.game_confirmed && ending.changed() {
+            print_message(format_args!(
+                "Ending value changed from {:04X} to {:04X}",
+                ending.old, ending.current
+            ));
+        }
+    }
 
-    // Reset the buffer IGT variables when the timer is stopped
-    if timer::state() == TimerState::NotRunning {
-        watchers.accumulated_igt = Duration::ZERO;
-        watchers.buffer_igt = Duration::ZERO;
+    // Keep track of the IGT that passes while HP is zero, for the pause on death option
+    if let (true, Some(hp), Some(now)) =
+        (watchers.game_confirmed, watchers.hp.pair, run_igt(watchers))
+    {
+        match watchers.run.died_at {
+            Some(died_at) if hp.current != 0 => {
+                // Any of it during a timer pause is already left out, see sync_run_state()
+                let until = watchers
+                    .run
+                    .timer_paused_at
+                    .map_or(now, |paused_at| paused_at.max(died_at).min(now));
+                watchers.run.death_igt += until - died_at;
+                watchers.run.died_at = None;
+            }
+            None if hp.changed_to(&0) => watchers.run.died_at = Some(now),
+            _ => (),
+        }
     }
 
-    if let Some(igt) = &watchers.igt.pair {
-        if igt.old > igt.current {
-            watchers.accumulated_igt += igt.old - watchers.buffer_igt;
-            watchers.buffer_igt = igt.current;
+    true
+}
+
+// Takes each slot of the new read only if it matches the previous read, otherwise keeps the
+// slot's settled value
+fn settle(last_read: &mut [u16; 12], read: [u16; 12], settled: &Watcher<[u16; 12]>) -> [u16; 12] {
+    let settled = settled.pair.map_or(read, |pair| pair.current);
+    let previous = core::mem::replace(last_read, read);
+    core::array::from_fn(|i| {
+        if read[i] == previous[i] {
+            read[i]
+        } else {
+            settled[i]
+        }
+    })
+}
+
+// Repeats a watcher's current value, so nothing reads as having changed on an update where the
+// value couldn't be read
+fn hold<T: Copy>(watcher: &mut Watcher<T>) {
+    if let Some(pair) = watcher.pair {
+        watcher.update_infallible(pair.current);
+    }
+}
+
+// Keeps the per-run state in step with the timer, given the timer state and the in-game counter's
+// current value rather than reading either, so this only depends on its arguments. Resets the run
+// state when the timer is stopped, returning the attempt that was cleared. While the timer is
+// paused, keeps track of the IGT that passes for game_time() to leave out.
+fn sync_run_state(
+    run: &mut RunState,
+    timer_state: TimerState,
+    igt: Option<Duration>,
+) -> Option<RunState> {
+    let previous = (timer_state == TimerState::NotRunning).then(|| reset_run_state(run));
+
+    let Some(now) = igt.map(|igt| run.igt(igt)) else {
+        return previous;
+    };
+    match (timer_state, run.timer_paused_at) {
+        (TimerState::Paused, None) => run.timer_paused_at = Some(now),
+        (TimerState::Paused, Some(_)) => (),
+        (_, Some(paused_at)) => {
+            run.timer_paused_igt += now - paused_at;
+            run.timer_paused_at = None;
+            // A death still going on is only counted up to the pause, and again from here on,
+            // so the paused stretch isn't left out twice
+            if let Some(died_at) = run.died_at {
+                if died_at < paused_at {
+                    run.death_igt += paused_at - died_at;
+                }
+                run.died_at = Some(now);
+            }
+        }
+        _ => (),
+    }
+    previous
+}
+
+// Clears everything tracked for the current attempt, returning what it was. Runs on every tick
+// the timer is stopped and again the moment it starts, so nothing from a previous attempt can
+// leak into the new one.
+fn reset_run_state(run: &mut RunState) -> RunState {
+    core::mem::take(run)
+}
+
+// Takes the cleared attempt's split times off the layout and shows the new one's load stats
+fn clear_run_variables(previous: &RunState, run: &RunState) {
+    for index in 0..previous.splits.min(SPLIT_TIMES as u32) {
+        set_variable(split_time_key(index).as_str(), format_args!(""));
+    }
+    publish_load_stats(run);
+}
+
+// Sets up the run state for a run that was just started, by the auto start or by hand
+fn begin_run(watchers: &mut Watchers, settings: &Settings) {
+    let previous = reset_run_state(&mut watchers.run);
+    clear_run_variables(&previous, &watchers.run);
+
+    // NG+ files carry the previous clear's IGT over, so measure from the value it had when the
+    // run started
+    if settings.new_game_plus {
+        if let Some(igt) = &watchers.igt.pair {
+            watchers.run.buffer_igt = igt.current;
+        }
+    }
+}
+
+// Keeps the time each split happened at, published as split_1, split_2, ... in split order so
+// tools can compare runs without reading the splits file. The in-game counter's value is kept
+// alongside it for undo_rewound_splits().
+fn record_split_time(run: &mut RunState, igt: Duration, time: Duration) {
+    let index = run.splits;
+    if let Some(slot) = run.split_times.get_mut(index as usize) {
+        *slot = time;
+        run.split_igt[index as usize] = igt;
+        set_variable(
+            split_time_key(index).as_str(),
+            format_args!("{}", Clock(time)),
+        );
+    }
+}
+
+// The time a split is published with: the game time reported to LiveSplit, so it follows the
+// same settings. With the real time modes LiveSplit keeps the time itself and it can't be read
+// back, so the run's IGT stands in for it.
+fn split_time(watchers: &Watchers, settings: &Settings) -> Option<Duration> {
+    game_time(watchers, settings).or_else(|| run_igt(watchers))
+}
+
+// A save loaded from before a split rewinds the in-game counter below the value it had at that
+// split, which can't happen by backtracking, so those splits are taken back. Splits past the
+// ones kept in split_igt aren't known well enough to be undone.
+fn undo_rewound_splits(run: &mut RunState, practice: bool) {
+    let Some(rewound_to) = run.rewound_to else {
+        return;
+    };
+    while run.splits > 0 && run.splits as usize <= SPLIT_TIMES {
+        let index = run.splits - 1;
+        if run.split_igt[index as usize] <= rewound_to {
+            break;
+        }
+        if practice {
+            print_message(format_args!("Practice: undo split {}", run.splits));
+        } else {
+            timer::undo_split();
+        }
+        set_variable(split_time_key(index).as_str(), format_args!(""));
+        run.splits = index;
+    }
+}
+
+fn split_time_key(index: u32) -> Text<16> {
+    let mut key = Text::new();
+    let _ = write!(key, "split_{}", index + 1);
+    key
+}
+
+// Summary of the run, logged once when it finishes
+fn log_run_stats(watchers: &Watchers) {
+    print_message(format_args!(
+        "Run finished. Game time: {:?}, IGT: {:?}, splits: {}, loads: {} ({} real time)",
+        watchers.run.last_game_time.map(Clock),
+        run_igt(watchers).map(Clock),
+        watchers.run.splits,
+        watchers.run.loads,
+        Clock(watchers.run.load_time),
+    ));
+}
+
+// Shows the freezes counted as loads this run, as the total and the shortest, longest and latest
+fn publish_load_stats(run: &RunState) {
+    for (key, load) in [
+        ("load_last", run.last_load),
+        ("load_shortest", run.shortest_load),
+        ("load_longest", run.longest_load),
+    ] {
+        match load {
+            Some(load) => set_variable(key, format_args!("{}", Clock(load))),
+            None => set_variable(key, format_args!("")),
         }
     }
+    set_variable("load_total", format_args!("{}", Clock(run.load_time)));
+}
+
+// Logs everything the splitter currently knows about the game, so it can be pasted into a bug
+// report
+fn dump(watchers: &Watchers, settings: &Settings) {
+    let current = |pair: Option<asr::watcher::Pair<u16>>| pair.map(|pair| pair.current);
+    print_message(format_args!(
+        "Game code: {:02X?}, confirmed: {}",
+        watchers.gamecode, watchers.game_confirmed
+    ));
+    print_message(format_args!(
+        "HP: {:?}, map: {:?}, ending: {:?}",
+        current(watchers.hp.pair),
+        current(watchers.map_id.pair),
+        current(watchers.ending.pair),
+    ));
+    print_message(format_args!(
+        "IGT: {:?}, run IGT: {:?}, game time: {:?}",
+        watchers.igt.pair.map(|pair| Clock(pair.current)),
+        run_igt(watchers).map(Clock),
+        game_time(watchers, settings).map(Clock),
+    ));
+    for (name, slots) in [
+        ("Items", &watchers.inventory),
+        ("Quantities", &watchers.quantities),
+        ("States", &watchers.states),
+    ] {
+        print_message(format_args!(
+            "{}: {:?}",
+            name,
+            slots.pair.map(|pair| pair.current)
+        ));
+    }
+}
+
+// Starts the watchers over for a new attach, keeping the progress of the run in progress so an
+// emulator being closed and reopened (or re-attached to) doesn't set the timer back. The game
+// time played so far gets banked, and the IGT is counted on from whatever it reads first on the
+// new attach. If the timer isn't running, all of this is cleared again on the next update.
+// The very first attach goes through here too, so attaching to a run already in progress
+// counts game time from the first IGT read rather than jumping to the file's whole IGT.
+fn carry_run_state(previous: Watchers) -> Watchers {
+    let accumulated_igt = if previous.run.rebase_igt {
+        previous.run.accumulated_igt
+    } else {
+        previous
+            .igt
+            .pair
+            .map_or(previous.last_igt_read, |igt| igt.current)
+            + previous.run.accumulated_igt
+            - previous.run.buffer_igt
+    };
+    // Whatever was in progress as the connection was lost (a queued split, a load, the reset
+    // delay) starts over
+    Watchers {
+        disc: previous.disc,
+        run: RunState {
+            accumulated_igt,
+            rebase_igt: true,
+            ending_age: None,
+            rewound_to: None,
+            rewind_age: None,
+            queued_splits: 0,
+            queued_doors: 0,
+            load_started: None,
+            reset_since: None,
+            game_time_paused: None,
+            ..previous.run
+        },
+        ..Watchers::default()
+    }
+}
+
+// Restarts the count when the event happens, otherwise ages it until it falls out of the window
+fn event_age(age: Option<u8>, happened: bool, window: u8) -> Option<u8> {
+    if happened {
+        Some(0)
+    } else {
+        age.map(|age| age + 1).filter(|&age| age <= window)
+    }
+}
+
+// Dumps don't all store the game code with the same punctuation, case or padding, so only its
+// first nine letters and digits are matched, which is the whole code. Separators and trailing
+// nulls or spaces drop out, e.g. "slus-008.98", "SLUS 008.98" and "SLUS_00898\0" all become
+// "SLUS00898".
+fn normalize_gamecode(gamecode: [u8; 11]) -> [u8; 9] {
+    let mut code = [0; 9];
+    let alphanumeric = gamecode.iter().filter(|byte| byte.is_ascii_alphanumeric());
+    for (slot, byte) in code.iter_mut().zip(alphanumeric) {
+        *slot = byte.to_ascii_uppercase();
+    }
+    code
+}
+
+// Some emulators expose a larger region that mirrors RAM, where the game code can match while
+// the rest of the offsets land in the wrong place. Before anything is trusted after attaching,
+// check that the inventory holds nothing but empty slots and IDs in the known item range.
+fn layout_plausible(game: &impl Memory, offsets: &Offsets) -> bool {
+    game.read_inventory(offsets.item_1)
+        .is_some_and(|inventory| inventory.iter().all(|&[item, _, _]| known_item(item)))
+}
+
+// An escape hatch for an item ID that turns out to be shared with something else: the IDs listed
+// in the "ignored_items" entry of the settings map (e.g. "111, 308") are read as empty slots, so
+// no item check ever sees them. The settings GUI has no text field, so the entry has to be added
+// to the saved settings by hand. It's read once on startup, up to IGNORED_ITEMS IDs.
+fn ignored_items() -> [u16; IGNORED_ITEMS] {
+    let mut ignored = [0; IGNORED_ITEMS];
+    let Some(Ok(list)) = asr::settings::Map::load()
+        .get("ignored_items")
+        .and_then(|value| value.get_array_string::<64>())
+    else {
+        return ignored;
+    };
+    let ids = list
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter_map(|id| id.parse().ok());
+    for (slot, id) in ignored.iter_mut().zip(ids) {
+        *slot = id;
+    }
+    if ignored != [0; IGNORED_ITEMS] {
+        print_message(format_args!("Ignoring item IDs {:?}", ignored));
+    }
+    ignored
+}
+
+// The map ID the chosen room start waits for, from the "start_map" entry of the settings map
+// (e.g. "41", as shown by the room variable). Like ignored_items() it has to be added to the saved
+// settings by hand and is read once on startup.
+fn start_map() -> Option<u16> {
+    let map = asr::settings::Map::load()
+        .get("start_map")
+        .and_then(|value| value.get_array_string::<8>())
+        .and_then(|value| value.ok()?.trim().parse().ok());
+    if let Some(map) = map {
+        print_message(format_args!("Chosen room start on map {map}"));
+    }
+    map
+}
+
+// Empty slots and a range around every item ID known so far. The key item table's IDs are 111
+// (the M82A1) and 302 to 435. No other weapon or consumable ID has been identified, so the range
+// is padded to 100..500 for those rather than fitted to the table. The layout check only has to
+// tell an inventory apart from unrelated memory, so a loose range is enough.
+fn known_item(item: u16) -> bool {
+    item == EMPTY_SLOT || (100..500).contains(&item)
+}
+
+// Forgets every value read from memory, leaving the run accounting untouched
+fn clear_watchers(watchers: &mut Watchers) {
+    watchers.game_confirmed = false;
+    watchers.hp.pair = None;
+    watchers.igt.pair = None;
+    watchers.map_id.pair = None;
+    watchers.inventory.pair = None;
+    watchers.quantities.pair = None;
+    watchers.states.pair = None;
+    watchers.ending.pair = None;
 }
 
 // If the setting "start" is not selected, nothing will happen
-// Checks to see if the current IGT > 0 and the old IGT == 0
-fn start(watchers: &Watchers, settings: &Settings) -> bool {
+// New game: checks to see if the current IGT > 0 and the old IGT == 0
+// Room entry: checks for a map change while the IGT is already counting
+// Gain of control: checks for the map a new game started on being left
+fn start(watchers: &Watchers, settings: &Settings, start_map: Option<u16>) -> bool {
     if !settings.start {
         return false;
     }
 
-    settings.start
-        && watchers
-            .igt
-            .pair
-            .is_some_and(|pair| pair.changed_from(&Duration::ZERO))
+    match settings.start_mode {
+        // Only from the first second on, so closing a menu the IGT read zero in doesn't count.
+        // An NG+ file starts from the IGT the last clear carried over, so with NG+ on any
+        // value does.
+        StartMode::NewGame => watchers.igt.pair.is_some_and(|pair| {
+            pair.changed_from(&Duration::ZERO)
+                && (pair.current < Duration::SECOND || settings.new_game_plus)
+        }),
+        // Only count room changes while the game is running, so the title screen and the
+        // first room of a file being loaded don't start the timer
+        StartMode::RoomEntry => {
+            watchers
+                .igt
+                .pair
+                .is_some_and(|pair| pair.old != Duration::ZERO)
+                && watchers.map_id.pair.is_some_and(|pair| pair.changed())
+        }
+        StartMode::GainOfControl => watchers.new_game_map.is_some_and(|map| {
+            watchers
+                .map_id
+                .pair
+                .is_some_and(|pair| pair.changed() && pair.old == map)
+        }),
+        // Loading a save made in the room counts as entering it, so a segment can be started
+        // either way
+        StartMode::ChosenRoom => start_map.is_some_and(|map| {
+            watchers
+                .map_id
+                .pair
+                .is_some_and(|pair| pair.changed_to(&map))
+        }),
+    }
+}
+
+// Splits on every map change, unless the previous door split was less than the configured
+// interval of IGT ago. The moves onto the ending map and on to the credits are left to the end
+// and credits splits, so those don't split twice. Forward only assumes map IDs go up along the
+// route, which is only a rough rule, so it can skip route doors and still split on backtracking.
+fn door_split(watchers: &Watchers, settings: &Settings) -> bool {
+    settings.door_split
+        && watchers.game_confirmed
+        && watchers.map_id.pair.is_some_and(|i| {
+            i.changed()
+                && !ending_map(watchers, settings, i.current)
+                && (!settings.door_split_forward || i.current > i.old)
+        })
+        && !credits(watchers, settings)
+        && match (watchers.run.last_door_split, run_igt(watchers)) {
+            (Some(last), Some(now)) => now - last >= settings.door_split_interval.duration(),
+            _ => true,
+        }
+}
+
+// Whether entering this map is part of the ending, which the end split covers on its own. With
+// any map allowed as the ending, only a map entered after the ending value was set counts.
+fn ending_map(watchers: &Watchers, settings: &Settings, map_id: u16) -> bool {
+    settings.end
+        && watchers.disc == Some(Disc::Two)
+        && settings.end_maps.contains(map_id)
+        && (settings.end_maps != EndMaps::Any || watchers.run.ending_age.is_some())
+}
+
+// Both halves of the ending must have happened within ENDING_WINDOW updates of each other, and at
+// least one of them this update so the split only fires once. It isn't known whether the Bad End
+// reached by running out of time sets the ending value, so that one can also go by its map alone.
+// Both endings are on Disc 2, so a Disc 1 room that happens to share an ending map's ID can't
+// finish the run.
+fn ending(watchers: &Watchers, settings: &Settings) -> bool {
+    let Some(map_id) = watchers.map_id.pair else {
+        return false;
+    };
+    if !settings.end
+        || watchers.disc != Some(Disc::Two)
+        || !settings.end_maps.contains(map_id.current)
+    {
+        return false;
+    }
+    if settings.bad_end_map_only && map_id.current == 110 {
+        return watchers.game_confirmed && map_id.changed();
+    }
+    watchers
+        .run
+        .ending_age
+        .zip(watchers.map_change_age)
+        .is_some_and(|(ending, map_change)| ending == 0 || map_change == 0)
 }
 
-fn split(watchers: &Watchers, settings: &Settings) -> bool {
-    if settings.door_split && watchers.map_id.pair.is_some_and(|i| i.changed()) {
-        true
-    } else if settings.end
-        && watchers.ending.pair.is_some_and(|i| i.changed_to(&0xFFFF))
+// No separate credits state is known. Assuming the ending value stays set through them, leaving
+// one of the ending maps with it set is taken as the credits starting. It has to have been set
+// during this attempt, not left over from the last one.
+fn credits(watchers: &Watchers, settings: &Settings) -> bool {
+    settings.credits
+        && watchers.disc == Some(Disc::Two)
+        && watchers.run.ending_reached
+        && watchers.ending.pair.is_some_and(|i| i.current == 0xFFFF)
         && watchers
             .map_id
             .pair
-            .is_some_and(|i| i.changed() && (i.current == 123 || i.current == 110))
-    {
-        true
-    } else {
-        watchers.inventory.pair.is_some_and(|inventory| {
-        (settings.keno && inventory.check(|arr| arr.contains(&309)))
-            || (settings.susie && inventory.check(|arr| arr.contains(&303)))
-            || (settings.nancy && inventory.check(|arr| arr.contains(&304)))
-            || (settings.cheryl && inventory.check(|arr| arr.contains(&302)))
-            || (settings.stagekey && inventory.check(|arr| arr.contains(&310)))
-            || (settings.leagan && inventory.check(|arr| arr.contains(&305)))
-            || (settings.attract && inventory.check(|arr| arr.contains(&335)))
-            || (settings.museum && inventory.check(|arr| arr.contains(&336)))
-            || (settings.moon && inventory.check(|arr| arr.contains(&337)))
-            || (settings.evil && inventory.check(|arr| arr.contains(&340)))
-            || (settings.spear && inventory.check(|arr| arr.contains(&308)))
-            || (settings.cardc && inventory.check(|arr| arr.contains(&338)))
-            || (settings.cardd && inventory.check(|arr| arr.contains(&339)))
-            || (settings.sydney && inventory.check(|arr| arr.contains(&306)))
-            || (settings.card9 && inventory.check(|arr| arr.contains(&311)))
-            || (settings.bluehand && inventory.check(|arr| arr.contains(&331)))
-            || (settings.redhand && inventory.check(|arr| arr.contains(&332)))
-            || (settings.panel1 && inventory.check(|arr| arr.contains(&359)))
-            || (settings.event && inventory.check(|arr| arr.contains(&363)))
-            || (settings.panel2 && inventory.check(|arr| arr.contains(&364)))
-            || (settings.panel4 && inventory.check(|arr| arr.contains(&366)))
-            || (settings.panel6 && inventory.check(|arr| arr.contains(&368)))
-            || (settings.ykey && inventory.check(|arr| arr.contains(&343)))
-            || (settings.d4 && inventory.check(|arr| arr.contains(&383)))
-            || (settings.lot && inventory.check(|arr| arr.contains(&385)))
-            || (settings.camp && inventory.check(|arr| arr.contains(&392)))
-            || (settings.small && inventory.check(|arr| arr.contains(&393)))
-            || (settings.fork && inventory.check(|arr| arr.contains(&434)))
-            || (settings.log && inventory.check(|arr| arr.contains(&408)))
-            || (settings.guest && inventory.check(|arr| arr.contains(&435)))
-            || (settings.shower && inventory.check(|arr| arr.contains(&413)))
-            || (settings.shelf && inventory.check(|arr| arr.contains(&403)))
-            || (settings.bourbon && inventory.check(|arr| arr.contains(&415)))
-            || (settings.marlin && inventory.check(|arr| arr.contains(&405)))
-            || (settings.chain && inventory.check(|arr| arr.contains(&404)))
-            || (settings.observ && inventory.check(|arr| arr.contains(&428)))
-            || (settings.sterile && inventory.check(|arr| arr.contains(&429)))
-            || (settings.m8 && inventory.check(|arr| arr.contains(&111)))
-            || (settings.sin && inventory.check(|arr| arr.contains(&423)))
-            || (settings.fuse && inventory.check(|arr| arr.contains(&430)))
-        })
+            .is_some_and(|i| i.changed() && EndMaps::Both.contains(i.old))
+}
+
+// Counts the split conditions that fired this update. LiveSplit takes one split per update, so
+// when several fire together the extras are queued up in main() and split on the updates after.
+// Listed in priority order: end, doors, unique rooms, credits, disc swap, low HP, then items, with
+// each item condition counting on its own.
+fn split(watchers: &Watchers, settings: &Settings) -> u32 {
+    [
+        ending(watchers, settings),
+        door_split(watchers, settings),
+        // Unlike door splits, going back into a room that was already entered doesn't count
+        settings.unique_rooms.every().is_some_and(|every| {
+            watchers
+                .run
+                .unique_rooms
+                .pair
+                .is_some_and(|i| i.changed() && i.current % every == 0)
+        }),
+        credits(watchers, settings),
+        settings.split_on_disc_swap
+            && watchers
+                .run
+                .disc_swapped
+                .pair
+                .is_some_and(|i| i.changed_to(&true)),
+        // Only the drop below the threshold splits, so HP has to recover above it before the
+        // next fight can split again
+        watchers.game_confirmed
+            && settings.low_hp.threshold().is_some_and(|threshold| {
+                watchers
+                    .hp
+                    .pair
+                    .is_some_and(|i| i.old >= threshold && i.current < threshold)
+            }),
+    ]
+    .into_iter()
+    .filter(|&fired| fired)
+    .count() as u32
+        + items::split(watchers, settings)
+}
+
+// Queues up the splits that fired this update and takes the next one from the queue, returning
+// whether there was one to split on. A door split's time is taken here, once it actually splits.
+fn next_split(watchers: &mut Watchers, settings: &Settings) -> bool {
+    // The door split comes right after the end split, see split()
+    let door = door_split(watchers, settings).then(|| ending(watchers, settings) as u32);
+    let count = split(watchers, settings);
+    queue_splits(&mut watchers.run, count, door);
+    let Some(door) = take_split(&mut watchers.run) else {
+        return false;
+    };
+    if door {
+        watchers.run.last_door_split = run_igt(watchers);
+    }
+    true
+}
+
+// Queues this update's splits behind the ones still waiting. The door split's place among them
+// is kept, so the time of the last door split can be taken once it actually splits.
+fn queue_splits(run: &mut RunState, count: u32, door: Option<u32>) {
+    if let Some(door) = door {
+        run.queued_doors |= 1u32.checked_shl(run.queued_splits + door).unwrap_or(0);
     }
+    run.queued_splits += count;
+}
+
+// Takes the split at the front of the queue, if there is one, along with whether it's a door split
+fn take_split(run: &mut RunState) -> Option<bool> {
+    if run.queued_splits == 0 {
+        return None;
+    }
+    run.queued_splits -= 1;
+    let door = run.queued_doors & 1 != 0;
+    run.queued_doors >>= 1;
+    Some(door)
 }
 
 fn reset(_watchers: &Watchers, _settings: &Settings) -> bool {
     false
 }
 
+// A title screen flicker shouldn't throw away a run, so the reset condition has to hold for the
+// whole reset delay before the timer is actually reset
+fn reset_held(watchers: &mut Watchers, settings: &Settings) -> bool {
+    if !reset(watchers, settings) {
+        watchers.run.reset_since = None;
+        return false;
+    }
+
+    let since = *watchers.run.reset_since.get_or_insert_with(Instant::now);
+    Instant::now() - since >= settings.reset_delay.duration()
+}
+
 // Some(true) is equivelant to "return true"
-fn is_loading(_watchers: &Watchers, _settings: &Settings) -> Option<bool> {
-    Some(true)
+// In the IGT modes game time is never advanced by LiveSplit itself, so this stays paused
+// permanently. Any load removal has to happen inside game_time() rather than by unpausing here, or
+// loads would be counted twice. The real time modes let LiveSplit's clock run instead, and RTA
+// minus loads treats the IGT counter standing still for a while as a load. No menu flag is known,
+// so pause on menu treats the IGT standing still without the map changing as a menu being open,
+// which can also catch the start of a load until its map change comes through.
+fn is_loading(watchers: &Watchers, settings: &Settings) -> Option<bool> {
+    let frozen = watchers.igt_frozen_for >= LOAD_FREEZE_UPDATES;
+    match settings.timing_mode {
+        TimingMode::Igt | TimingMode::RawIgt => Some(true),
+        TimingMode::RtaNoLoads => Some(frozen),
+        TimingMode::Rta => Some(
+            settings.pause_on_menu
+                && frozen
+                && watchers.frozen_on_map == watchers.map_id.pair.map(|pair| pair.current),
+        ),
+    }
+}
+
+// Keeps game time paused through loads, going by is_loading(). Practice mode leaves the timer be.
+fn pause_for_loads(watchers: &mut Watchers, settings: &Settings) {
+    let is_loading = is_loading(watchers, settings).filter(|_| !settings.practice_mode);
+    if let Some(is_loading) = is_loading {
+        let paused = is_loading && !load_timed_out(watchers, settings);
+        set_game_time_paused(watchers, paused);
+    }
+}
+
+// Only pauses or resumes game time when that differs from the last call, rather than every update
+fn set_game_time_paused(watchers: &mut Watchers, paused: bool) {
+    if watchers.run.game_time_paused == Some(paused) {
+        return;
+    }
+    if paused {
+        timer::pause_game_time()
+    } else {
+        timer::resume_game_time()
+    }
+    watchers.run.game_time_paused = Some(paused);
+}
+
+// Loads in real time without loads are only inferred from the IGT standing still, so one that
+// lasts far longer than any real load is most likely a misread. Game time is resumed once that
+// happens, until the load ends, rather than staying paused for the rest of the run.
+fn load_timed_out(watchers: &mut Watchers, settings: &Settings) -> bool {
+    let Some(timeout) = settings.load_timeout.duration() else {
+        return false;
+    };
+    if settings.timing_mode != TimingMode::RtaNoLoads {
+        return false;
+    }
+
+    let since = *watchers.loading_since.get_or_insert_with(Instant::now);
+    if !watchers.load_timed_out && Instant::now() - since >= timeout {
+        print_message(format_args!(
+            "A load went on for too long, resuming game time"
+        ));
+        watchers.load_timed_out = true;
+    }
+    watchers.load_timed_out
+}
+
+// Game time model: the game's own IGT counter is the only source of time. It already excludes
+// loads, so no separate load accounting is layered on top. Loading a save (or dying) rewinds the
+// counter to the saved value, so every time it goes backwards the time played since the last
+// rewind is banked in accumulated_igt, and buffer_igt remembers the value it rewound to.
+// Pause on death freezes the reported time while HP is zero and leaves that stretch out of the
+// total afterwards. Pausing the LiveSplit timer does the same, so game time stands still while
+// it's paused even if the game keeps running. LiveSplit's game time is already permanently
+// paused (see is_loading), so this works on the reported value rather than through
+// pause_game_time.
+// The raw IGT mode reports the counter as it is instead, rewinds and all. The real time modes
+// return None so LiveSplit's own clock is left alone.
+fn game_time(watchers: &Watchers, settings: &Settings) -> Option<Duration> {
+    let game_time = match settings.timing_mode {
+        TimingMode::Igt => {
+            // If the IGT couldn't be read this tick, keep reporting the last time of this run
+            // instead of nothing
+            let Some(igt) = run_igt(watchers) else {
+                return watchers.run.last_game_time;
+            };
+            let frozen_at = watchers.run.timer_paused_at.unwrap_or(igt);
+            let game_time = if settings.pause_on_death {
+                watchers.run.died_at.unwrap_or(igt).min(frozen_at) - watchers.run.death_igt
+            } else {
+                frozen_at
+            };
+            game_time - watchers.run.timer_paused_igt
+        }
+        TimingMode::RawIgt => match &watchers.igt.pair {
+            Some(igt) => igt.current,
+            None => return watchers.run.last_game_time,
+        },
+        TimingMode::RtaNoLoads | TimingMode::Rta => return None,
+    };
+    // The frame count has sub-second precision the in-game clock doesn't show
+    let game_time = if settings.whole_seconds {
+        Duration::seconds(game_time.whole_seconds())
+    } else {
+        game_time
+    };
+    // Changing the settings game time depends on can set it back, so only compare against a
+    // game time taken with the same ones. The raw counter goes back on every loaded save.
+    debug_assert!(
+        settings.timing_mode == TimingMode::RawIgt
+            || watchers.run.last_game_time_settings != Some(game_time_settings(settings))
+            || watchers
+                .run
+                .last_game_time
+                .is_none_or(|last| game_time >= last),
+        "game time went backwards from {:?} to {:?}",
+        watchers.run.last_game_time.map(Clock),
+        Clock(game_time)
+    );
+    Some(game_time)
+}
+
+// The settings that change what game_time() reports
+fn game_time_settings(settings: &Settings) -> (TimingMode, bool, bool) {
+    (
+        settings.timing_mode,
+        settings.whole_seconds,
+        settings.pause_on_death,
+    )
+}
+
+// Total IGT played this run, carried across every rewind of the in-game counter
+fn run_igt(watchers: &Watchers) -> Option<Duration> {
+    Some(watchers.run.igt(watchers.igt.pair?.current))
+}
+
+impl RunState {
+    // The run IGT for the in-game counter's current value, see run_igt()
+    fn igt(&self, igt: Duration) -> Duration {
+        igt + self.accumulated_igt - self.buffer_igt
+    }
+}
+
+// Compact code for the current split configuration, so a route can be shared as one token. It
+// starts with SETTINGS_CODE_VERSION and a dot, then each choice is one hex digit, in the order the
+// settings list them, then after a dash the checkboxes in the same order packed four to a hex
+// digit, first checkbox in the lowest bit. Headings and the snapshot checkbox are left out. Item
+// settings only appear in the full build, so codes don't carry over between builds.
+struct SettingsCode<'a>(&'a Settings);
+
+// Goes up whenever a setting is added to the code, removed from it or moved, so a code from an
+// older version can't be read with the wrong meaning for its digits
+const SETTINGS_CODE_VERSION: u32 = 1;
+
+impl fmt::Display for SettingsCode<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let settings = self.0;
+        write!(f, "{}.", SETTINGS_CODE_VERSION)?;
+        let choices = [
+            settings.preset as u8,
+            settings.timing_mode as u8,
+            settings.load_timeout as u8,
+            settings.reset_delay as u8,
+            settings.update_rate as u8,
+            settings.start_mode as u8,
+            settings.end_maps as u8,
+            settings.door_split_interval as u8,
+            settings.unique_rooms as u8,
+            settings.low_hp as u8,
+        ];
+        for choice in choices.into_iter().chain(items::choices(settings)) {
+            write!(f, "{:X}", choice)?;
+        }
+        f.write_str("-")?;
+
+        let toggles = [
+            settings.pause_on_stale,
+            settings.pause_on_death,
+            settings.pause_on_menu,
+            settings.smooth_igt,
+            settings.whole_seconds,
+            settings.practice_mode,
+            settings.auto_undo_split,
+            settings.start,
+            settings.manual_arm,
+            settings.new_game_plus,
+            settings.end,
+            settings.bad_end_map_only,
+            settings.credits,
+            settings.door_split,
+            settings.door_split_forward,
+            settings.split_on_disc_swap,
+        ];
+        let mut nibble = 0;
+        let mut bits = 0;
+        for enabled in toggles.into_iter().chain(items::toggles(settings)) {
+            nibble |= (enabled as u8) << bits;
+            bits += 1;
+            if bits == 4 {
+                write!(f, "{:X}", nibble)?;
+                nibble = 0;
+                bits = 0;
+            }
+        }
+        if bits > 0 {
+            write!(f, "{:X}", nibble)?;
+        }
+        Ok(())
+    }
 }
 
-fn game_time(watchers: &Watchers, _settings: &Settings) -> Option<Duration> {
-    Some(watchers.igt.pair?.current + watchers.accumulated_igt - watchers.buffer_igt)
+// Fixed size text buffer, so values can be formatted without an allocator.
+// Anything that doesn't fit is cut off.
+struct Text<const N: usize> {
+    buf: [u8; N],
+    len: usize,
+}
+
+impl<const N: usize> Text<N> {
+    fn new() -> Self {
+        Self {
+            buf: [0; N],
+            len: 0,
+        }
+    }
+
+    fn as_str(&self) -> &str {
+        core::str::from_utf8(&self.buf[..self.len]).unwrap_or_default()
+    }
+}
+
+impl<const N: usize> Write for Text<N> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let mut end = s.len().min(N - self.len);
+        while !s.is_char_boundary(end) {
+            end -= 1;
+        }
+        self.buf[self.len..self.len + end].copy_from_slice(&s.as_bytes()[..end]);
+        self.len += end;
+        Ok(())
+    }
+}
+
+fn set_variable(key: &str, value: fmt::Arguments<'_>) {
+    let mut text = Text::<64>::new();
+    let _ = text.write_fmt(value);
+    timer::set_variable(key, text.as_str());
+}
+
+// Names of the maps that have been identified so far. Only the ending maps are known for certain,
+// so add rooms here as they get documented.
+const ROOM_NAMES: &[(u16, &str)] = &[(110, "Bad Ending"), (123, "Good Ending")];
+
+// Displays a map ID as its room name, or as the bare number for rooms not named yet
+struct Room(u16);
+
+impl fmt::Display for Room {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match ROOM_NAMES.iter().find(|&&(map_id, _)| map_id == self.0) {
+            Some((_, name)) => f.write_str(name),
+            None => write!(f, "Map {}", self.0),
+        }
+    }
+}
+
+fn print_message(message: fmt::Arguments<'_>) {
+    let mut text = Text::<128>::new();
+    let _ = text.write_fmt(message);
+    runtime::print_message(text.as_str());
+}
+
+// Displays a duration as h:mm:ss.mmm
+struct Clock(Duration);
+
+impl fmt::Debug for Clock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+impl fmt::Display for Clock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let millis = self.0.whole_milliseconds();
+        if millis < 0 {
+            f.write_str("-")?;
+        }
+        let millis = millis.unsigned_abs();
+        write!(
+            f,
+            "{}:{:02}:{:02}.{:03}",
+            millis / 3_600_000,
+            millis / 60_000 % 60,
+            millis / 1000 % 60,
+            millis % 1000,
+        )
+    }
 }
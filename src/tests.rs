@@ -0,0 +1,1249 @@
+// Host tests, run with `cargo test --target <host triple>` as the default target is wasm. Only the
+// logic is tested here, the runtime itself is swapped for the stand-ins below.
+
+use std::collections::BTreeMap;
+
+use asr::time::Duration;
+
+use super::*;
+
+pub(crate) mod runtime {
+    pub(crate) fn print_message(_text: &str) {}
+
+    pub(crate) mod timer {
+        use std::cell::RefCell;
+
+        pub(crate) use asr::timer::TimerState;
+
+        // Only reached from main(), the tests pass the timer state in themselves
+        pub(crate) fn state() -> TimerState {
+            TimerState::NotRunning
+        }
+
+        pub(crate) fn set_variable(_key: &str, _value: &str) {}
+
+        pub(crate) fn undo_split() {}
+
+        thread_local! {
+            static GAME_TIME_CALLS: RefCell<Vec<bool>> = const { RefCell::new(Vec::new()) };
+        }
+
+        pub(crate) fn pause_game_time() {
+            GAME_TIME_CALLS.with_borrow_mut(|calls| calls.push(true));
+        }
+
+        pub(crate) fn resume_game_time() {
+            GAME_TIME_CALLS.with_borrow_mut(|calls| calls.push(false));
+        }
+
+        // Takes the pause (true) and resume (false) calls made since the last time
+        pub(crate) fn game_time_calls() -> Vec<bool> {
+            GAME_TIME_CALLS.take()
+        }
+    }
+
+    pub(crate) mod time_util {
+        use core::ops::Sub;
+        use std::cell::Cell;
+
+        use asr::time::Duration;
+
+        thread_local! {
+            static NOW: Cell<Duration> = const { Cell::new(Duration::ZERO) };
+        }
+
+        // A clock that only moves when a test advances it
+        #[derive(Clone, Copy)]
+        pub(crate) struct Instant(Duration);
+
+        impl Instant {
+            pub(crate) fn now() -> Self {
+                Self(NOW.get())
+            }
+
+            pub(crate) fn advance(by: Duration) {
+                NOW.set(NOW.get() + by);
+            }
+        }
+
+        impl Sub for Instant {
+            type Output = Duration;
+
+            fn sub(self, earlier: Self) -> Duration {
+                self.0 - earlier.0
+            }
+        }
+    }
+}
+
+// Every setting at its default
+pub(crate) fn settings() -> Settings {
+    Settings {
+        preset: Preset::Custom,
+        _general: true,
+        pause_on_stale: true,
+        timing_mode: TimingMode::Igt,
+        load_timeout: LoadTimeout::OneMinute,
+        reset_delay: ResetDelay::Off,
+        pause_on_death: false,
+        pause_on_menu: false,
+        smooth_igt: false,
+        whole_seconds: false,
+        update_rate: UpdateRate::Full,
+        practice_mode: false,
+        auto_undo_split: false,
+        dump: false,
+        _condit: true,
+        start: true,
+        start_mode: StartMode::NewGame,
+        manual_arm: false,
+        new_game_plus: false,
+        _ending: true,
+        end: true,
+        end_maps: EndMaps::Both,
+        bad_end_map_only: false,
+        credits: false,
+        _doors: true,
+        door_split: false,
+        door_split_interval: DoorSplitInterval::Off,
+        door_split_forward: false,
+        unique_rooms: UniqueRooms::Off,
+        _disc_swap: true,
+        split_on_disc_swap: false,
+        _health: true,
+        low_hp: LowHp::Off,
+        #[cfg(not(feature = "doors-only"))]
+        _items: true,
+        #[cfg(not(feature = "doors-only"))]
+        key_split: items::KeySplit::Pickup,
+        #[cfg(not(feature = "doors-only"))]
+        keno: false,
+        #[cfg(not(feature = "doors-only"))]
+        susie: false,
+        #[cfg(not(feature = "doors-only"))]
+        nancy: false,
+        #[cfg(not(feature = "doors-only"))]
+        cheryl: false,
+        #[cfg(not(feature = "doors-only"))]
+        stagekey: false,
+        #[cfg(not(feature = "doors-only"))]
+        leagan: false,
+        #[cfg(not(feature = "doors-only"))]
+        attract: false,
+        #[cfg(not(feature = "doors-only"))]
+        museum: false,
+        #[cfg(not(feature = "doors-only"))]
+        moon: false,
+        #[cfg(not(feature = "doors-only"))]
+        evil: false,
+        #[cfg(not(feature = "doors-only"))]
+        spear: false,
+        #[cfg(not(feature = "doors-only"))]
+        cardc: false,
+        #[cfg(not(feature = "doors-only"))]
+        cardd: false,
+        #[cfg(not(feature = "doors-only"))]
+        sydney: false,
+        #[cfg(not(feature = "doors-only"))]
+        card9: false,
+        #[cfg(not(feature = "doors-only"))]
+        bluehand: false,
+        #[cfg(not(feature = "doors-only"))]
+        redhand: false,
+        #[cfg(not(feature = "doors-only"))]
+        panel1: false,
+        #[cfg(not(feature = "doors-only"))]
+        event: false,
+        #[cfg(not(feature = "doors-only"))]
+        panel2: false,
+        #[cfg(not(feature = "doors-only"))]
+        panel4: false,
+        #[cfg(not(feature = "doors-only"))]
+        panel6: false,
+        #[cfg(not(feature = "doors-only"))]
+        ykey: false,
+        #[cfg(not(feature = "doors-only"))]
+        d4: false,
+        #[cfg(not(feature = "doors-only"))]
+        lot: false,
+        #[cfg(not(feature = "doors-only"))]
+        camp: false,
+        #[cfg(not(feature = "doors-only"))]
+        small: false,
+        #[cfg(not(feature = "doors-only"))]
+        fork: false,
+        #[cfg(not(feature = "doors-only"))]
+        log: false,
+        #[cfg(not(feature = "doors-only"))]
+        guest: false,
+        #[cfg(not(feature = "doors-only"))]
+        shower: false,
+        #[cfg(not(feature = "doors-only"))]
+        shelf: false,
+        #[cfg(not(feature = "doors-only"))]
+        bourbon: false,
+        #[cfg(not(feature = "doors-only"))]
+        marlin: false,
+        #[cfg(not(feature = "doors-only"))]
+        chain: false,
+        #[cfg(not(feature = "doors-only"))]
+        observ: false,
+        #[cfg(not(feature = "doors-only"))]
+        sterile: false,
+        #[cfg(not(feature = "doors-only"))]
+        m8: false,
+        #[cfg(not(feature = "doors-only"))]
+        sin: false,
+        #[cfg(not(feature = "doors-only"))]
+        fuse: false,
+        #[cfg(not(feature = "doors-only"))]
+        _sets: true,
+        #[cfg(not(feature = "doors-only"))]
+        vip_cards: false,
+        #[cfg(not(feature = "doors-only"))]
+        panels: false,
+        #[cfg(not(feature = "doors-only"))]
+        clock_hands: false,
+        #[cfg(not(feature = "doors-only"))]
+        lab_access: false,
+        #[cfg(not(feature = "doors-only"))]
+        key_item_target: items::KeyItemTarget::Off,
+        #[cfg(not(feature = "doors-only"))]
+        _rooms: true,
+        #[cfg(not(feature = "doors-only"))]
+        moon_room: false,
+        #[cfg(not(feature = "doors-only"))]
+        marlin_gate: false,
+        #[cfg(not(feature = "doors-only"))]
+        sin_used: false,
+        #[cfg(not(feature = "doors-only"))]
+        y_panel: false,
+        #[cfg(not(feature = "doors-only"))]
+        fuse_power: false,
+        #[cfg(not(feature = "doors-only"))]
+        evil_house: false,
+        #[cfg(not(feature = "doors-only"))]
+        show_stage: false,
+        #[cfg(not(feature = "doors-only"))]
+        attraction_area: false,
+        #[cfg(not(feature = "doors-only"))]
+        museum_area: false,
+        #[cfg(not(feature = "doors-only"))]
+        spear_area: false,
+        #[cfg(not(feature = "doors-only"))]
+        event_room: false,
+        #[cfg(not(feature = "doors-only"))]
+        campground: false,
+        #[cfg(not(feature = "doors-only"))]
+        forklift: false,
+        #[cfg(not(feature = "doors-only"))]
+        guesthouse: false,
+        #[cfg(not(feature = "doors-only"))]
+        park_areas: false,
+        #[cfg(not(feature = "doors-only"))]
+        _consumables: true,
+        #[cfg(not(feature = "doors-only"))]
+        bourbon_each: false,
+        #[cfg(not(feature = "doors-only"))]
+        _weapons: true,
+        #[cfg(not(feature = "doors-only"))]
+        chain_equipped: false,
+        #[cfg(not(feature = "doors-only"))]
+        m8_ammo: items::AmmoTarget::Off,
+    }
+}
+
+// A RAM dump in the fixture format: each line is a PS1 address followed by the bytes stored from
+// there on, all in hex, so a dump only has to hold the parts that are read. Blank lines and
+// anything after a # are skipped. Reads of bytes that aren't in the dump fail, just like reads
+// of memory the emulator can't reach.
+struct Dump(BTreeMap<u32, u8>);
+
+impl Dump {
+    fn parse(text: &str) -> Self {
+        let mut bytes = BTreeMap::new();
+        for line in text.lines() {
+            let line = line.split('#').next().unwrap().trim();
+            let Some((address, data)) = line.split_once(':') else {
+                continue;
+            };
+            let address = u32::from_str_radix(address.trim(), 16).unwrap();
+            for (offset, byte) in data.split_whitespace().enumerate() {
+                bytes.insert(
+                    address + offset as u32,
+                    u8::from_str_radix(byte, 16).unwrap(),
+                );
+            }
+        }
+        Self(bytes)
+    }
+
+    fn set(&mut self, address: u32, data: &[u8]) {
+        for (offset, &byte) in data.iter().enumerate() {
+            self.0.insert(address + offset as u32, byte);
+        }
+    }
+
+    // Makes the bytes unreadable, as if they were outside of what the emulator exposes
+    fn remove(&mut self, address: u32, len: u32) {
+        for address in address..address + len {
+            self.0.remove(&address);
+        }
+    }
+
+    fn bytes<const N: usize>(&self, address: u32) -> Option<[u8; N]> {
+        let mut bytes = [0; N];
+        for (offset, byte) in bytes.iter_mut().enumerate() {
+            *byte = *self.0.get(&(address + offset as u32))?;
+        }
+        Some(bytes)
+    }
+}
+
+impl Memory for Dump {
+    fn read_u16(&self, address: u32) -> Option<u16> {
+        self.bytes(address).map(u16::from_le_bytes)
+    }
+
+    fn read_u32(&self, address: u32) -> Option<u32> {
+        self.bytes(address).map(u32::from_le_bytes)
+    }
+
+    fn read_gamecode(&self, address: u32) -> Option<[u8; 11]> {
+        self.bytes(address)
+    }
+
+    fn read_inventory(&self, address: u32) -> Option<[[u16; 3]; 12]> {
+        let mut inventory = [[0; 3]; 12];
+        for (slot, fields) in inventory.iter_mut().enumerate() {
+            for (field, value) in fields.iter_mut().enumerate() {
+                *value = self.read_u16(address + (slot * 6 + field * 2) as u32)?;
+            }
+        }
+        Some(inventory)
+    }
+}
+
+// A Disc 1 save: 1:05 of IGT on map 12 with 100 HP, holding the Keno Ticket and the M82A1 (7
+// rounds, equipped)
+fn disc_1_dump() -> Dump {
+    Dump::parse(include_str!("../fixtures/disc_1.txt"))
+}
+
+// Runs the given number of updates against the dump, as many as it takes the settled values to
+// come through
+fn run_updates(dump: &Dump, watchers: &mut Watchers, settings: &Settings, updates: usize) -> bool {
+    let offsets = Offsets::new();
+    (0..updates).all(|_| {
+        update_loop(
+            dump,
+            &offsets,
+            watchers,
+            settings,
+            &[0; IGNORED_ITEMS],
+            TimerState::NotRunning,
+        )
+    })
+}
+
+#[test]
+fn fixture_offsets() {
+    let dump = disc_1_dump();
+    let mut watchers = Watchers::default();
+    assert!(run_updates(&dump, &mut watchers, &settings(), 2));
+
+    assert!(watchers.game_confirmed);
+    assert_eq!(watchers.gamecode, Some(*b"SLUS_008.98"));
+    assert!(watchers.disc == Some(Disc::One));
+    let current = |watcher: &Watcher<u16>| watcher.pair.map(|pair| pair.current);
+    assert_eq!(current(&watchers.hp), Some(100));
+    assert_eq!(current(&watchers.map_id), Some(12));
+    assert_eq!(current(&watchers.ending), Some(0));
+    assert_eq!(
+        watchers.igt.pair.map(|pair| pair.current),
+        Some(Duration::seconds(65))
+    );
+    let slots = |watcher: &Watcher<[u16; 12]>| watcher.pair.map(|pair| pair.current[..3].to_vec());
+    assert_eq!(slots(&watchers.inventory), Some(vec![309, 111, EMPTY_SLOT]));
+    assert_eq!(slots(&watchers.quantities), Some(vec![1, 7, 0]));
+    assert_eq!(slots(&watchers.states), Some(vec![0, 1, 0]));
+}
+
+#[test]
+fn fixture_wrong_game() {
+    let mut dump = disc_1_dump();
+    dump.set(Offsets::new().gamecode_ntsc, b"SLUS_000.00");
+    let mut watchers = Watchers::default();
+    assert!(run_updates(&dump, &mut watchers, &settings(), 2));
+
+    assert!(!watchers.game_confirmed);
+    assert!(watchers.map_id.pair.is_none() && watchers.inventory.pair.is_none());
+}
+
+#[test]
+fn fixture_unreadable() {
+    let dump = Dump(BTreeMap::new());
+    let mut watchers = Watchers::default();
+    assert!(!run_updates(&dump, &mut watchers, &settings(), 1));
+    assert!(watchers.gamecode.is_none());
+}
+
+// Drives update_loop() against a dump the test changes between updates, with the stand-in clock
+// moving on by one IGT frame per update
+struct Game {
+    dump: Dump,
+    watchers: Watchers,
+    settings: Settings,
+    timer_state: TimerState,
+}
+
+impl Game {
+    // Attached to the Disc 1 dump with the timer running, after enough updates for every
+    // value to have settled
+    fn new() -> Self {
+        let mut game = Self {
+            dump: disc_1_dump(),
+            watchers: Watchers::default(),
+            settings: settings(),
+            timer_state: TimerState::Running,
+        };
+        game.update();
+        game.update();
+        game
+    }
+
+    fn update(&mut self) {
+        self.update_after(1);
+    }
+
+    // Updates once the stand-in clock has moved on by the given number of IGT frames, as if the
+    // updates in between were skipped
+    fn update_after(&mut self, frames: u64) {
+        runtime::time_util::Instant::advance(frame_count::<30>(frames));
+        assert!(update_loop(
+            &self.dump,
+            &Offsets::new(),
+            &mut self.watchers,
+            &self.settings,
+            &[0; IGNORED_ITEMS],
+            self.timer_state,
+        ));
+    }
+
+    // Swaps in Disc 2, where the endings are
+    fn disc_2() -> Self {
+        let mut game = Self::new();
+        game.dump.set(Offsets::new().gamecode_ntsc, b"SLUS_011.99");
+        game.update();
+        game
+    }
+
+    // Starts a run from the current update on, the way main() does after the start condition
+    fn start_run(&mut self) {
+        self.timer_state = TimerState::Running;
+        begin_run(&mut self.watchers, &self.settings);
+    }
+
+    fn set_map(&mut self, map_id: u16) {
+        self.dump.set(Offsets::new().map_id, &map_id.to_le_bytes());
+    }
+
+    fn set_ending(&mut self, ending: u16) {
+        self.dump.set(Offsets::new().ending, &ending.to_le_bytes());
+    }
+
+    fn set_slot(&mut self, slot: u32, [item, quantity, state]: [u16; 3]) {
+        let address = Offsets::new().item_1 + slot * 6;
+        self.dump.set(address, &item.to_le_bytes());
+        self.dump.set(address + 2, &quantity.to_le_bytes());
+        self.dump.set(address + 4, &state.to_le_bytes());
+    }
+
+    fn set_igt(&mut self, frames: u32) {
+        self.dump.set(Offsets::new().igt, &frames.to_le_bytes());
+    }
+
+    fn igt_frames(&self) -> u32 {
+        self.dump.read_u32(Offsets::new().igt).unwrap()
+    }
+
+    // Plays on for the given number of IGT frames, one per update
+    fn play(&mut self, frames: u32) {
+        for _ in 0..frames {
+            self.set_igt(self.igt_frames().wrapping_add(1));
+            self.update();
+        }
+    }
+
+    // Plays on for the given number of updates, with the IGT moving on by several frames each
+    fn play_slowly(&mut self, frames_per_update: u32, updates: u32) {
+        for _ in 0..updates {
+            self.set_igt(self.igt_frames().wrapping_add(frames_per_update));
+            self.update_after(frames_per_update.into());
+        }
+    }
+
+    // Plays on for the given number of frames, adding up the split conditions that fire
+    fn count_splits(&mut self, frames: u32) -> u32 {
+        (0..frames)
+            .map(|_| {
+                self.play(1);
+                split(&self.watchers, &self.settings)
+            })
+            .sum()
+    }
+
+    // Plays on for the given number of frames, splitting the way main() does, one queued split
+    // per update. Lists the updates that split.
+    fn take_splits(&mut self, frames: u32) -> Vec<bool> {
+        (0..frames)
+            .map(|_| {
+                self.play(1);
+                next_split(&mut self.watchers, &self.settings)
+            })
+            .collect()
+    }
+
+    // The game time main() would report this update, kept as the run's last one the same way
+    fn game_time(&mut self) -> Option<Duration> {
+        let game_time = game_time(&self.watchers, &self.settings)?;
+        self.watchers.run.last_game_time = Some(game_time);
+        self.watchers.run.last_game_time_settings = Some(game_time_settings(&self.settings));
+        Some(game_time)
+    }
+}
+
+// The IGT counter is the only source of game time, and it stands still through loads. So a
+// segment with loads in it comes out as the frames played, however many updates the loads took.
+#[test]
+fn loads_left_out_of_game_time() {
+    let mut game = Game::new();
+    let start = game.game_time().unwrap();
+    for _ in 0..3 {
+        game.play(90);
+        for _ in 0..60 {
+            game.update();
+        }
+    }
+    game.play(1);
+
+    assert_eq!(game.game_time().unwrap() - start, frame_count::<30>(271));
+}
+
+// Counts the updates the ending split fires on while playing on for the given number of frames
+fn count_endings(game: &mut Game, frames: u32) -> usize {
+    (0..frames)
+        .filter(|_| {
+            game.play(1);
+            ending(&game.watchers, &game.settings)
+        })
+        .count()
+}
+
+#[test]
+fn ending_value_before_map() {
+    let mut game = Game::disc_2();
+    game.set_ending(0xFFFF);
+    assert_eq!(count_endings(&mut game, 20), 0);
+    game.set_map(123);
+    assert_eq!(count_endings(&mut game, 20), 1);
+}
+
+// With the option on, the Bad End's map ends the run without the ending value. The Good End's map
+// still needs it.
+#[test]
+fn bad_end_map_only() {
+    let mut game = Game::disc_2();
+    game.settings.bad_end_map_only = true;
+    game.set_map(123);
+    assert_eq!(count_endings(&mut game, 20), 0);
+    game.set_map(110);
+    assert_eq!(count_endings(&mut game, 20), 1);
+}
+
+// The ending maps' IDs may well be used for other rooms on Disc 1
+#[test]
+fn ending_map_on_disc_1() {
+    let mut game = Game::new();
+    game.settings.bad_end_map_only = true;
+    game.set_ending(0xFFFF);
+    game.set_map(123);
+    assert_eq!(count_endings(&mut game, 20), 0);
+    game.set_map(110);
+    assert_eq!(count_endings(&mut game, 20), 0);
+}
+
+#[test]
+fn ending_map_before_value() {
+    let mut game = Game::disc_2();
+    game.set_map(123);
+    assert_eq!(count_endings(&mut game, 20), 0);
+    game.set_ending(0xFFFF);
+    assert_eq!(count_endings(&mut game, 20), 1);
+}
+
+#[test]
+fn ending_halves_too_far_apart() {
+    let mut game = Game::disc_2();
+    game.set_ending(0xFFFF);
+    game.play(ENDING_WINDOW as u32 + 5);
+    game.set_map(123);
+    assert_eq!(count_endings(&mut game, 20), 0);
+}
+
+#[test]
+fn igt_wraps_around() {
+    let mut game = Game::new();
+    game.set_igt(u32::MAX - 2);
+    game.update();
+    let before = game.game_time().unwrap();
+    game.play(6);
+
+    assert_eq!(game.game_time().unwrap() - before, frame_count::<30>(6));
+    assert_eq!(game.watchers.run.buffer_igt, Duration::ZERO);
+}
+
+// Updates further apart than the IGT frames, as on a host that can't keep up with the update rate
+#[test]
+fn igt_wraps_around_with_slow_updates() {
+    let mut game = Game::new();
+    game.set_igt(u32::MAX - 5);
+    game.update();
+    let before = game.game_time().unwrap();
+    game.play_slowly(4, 3);
+
+    assert_eq!(game.game_time().unwrap() - before, frame_count::<30>(12));
+    assert_eq!(game.watchers.run.buffer_igt, Duration::ZERO);
+}
+
+#[test]
+fn loads_left_out_with_slow_updates() {
+    let mut game = Game::new();
+    let start = game.game_time().unwrap();
+    game.play_slowly(4, 20);
+    for _ in 0..LOAD_FREEZE_UPDATES * 2 {
+        game.update_after(4);
+    }
+    let rta_no_loads = Settings {
+        timing_mode: TimingMode::RtaNoLoads,
+        ..settings()
+    };
+    assert_eq!(is_loading(&game.watchers, &rta_no_loads), Some(true));
+    game.play_slowly(4, 20);
+
+    assert_eq!(game.game_time().unwrap() - start, frame_count::<30>(160));
+}
+
+#[test]
+fn rewind_from_near_the_wrap() {
+    let mut game = Game::new();
+    game.set_igt(u32::MAX - 2);
+    game.update();
+    let before = game.game_time().unwrap();
+    game.set_igt(1000);
+    game.update();
+
+    assert_eq!(game.watchers.run.buffer_igt, frame_count::<30>(1000));
+    assert_eq!(game.game_time(), Some(before));
+}
+
+#[test]
+fn new_game_plus_counts_from_the_start() {
+    let mut game = Game::new();
+    game.settings.new_game_plus = true;
+    game.timer_state = TimerState::NotRunning;
+    // Quit to the title screen, then start the NG+ file, which goes on from the IGT the last
+    // clear carried over rather than from zero
+    game.set_igt(0);
+    for _ in 0..ZERO_IGT_HOLD_UPDATES + 1 {
+        game.update();
+    }
+    game.set_igt(30 * 60 * 60 * 10);
+    assert!((0..3).any(|_| {
+        game.update();
+        start(&game.watchers, &game.settings, None)
+    }));
+    game.start_run();
+    game.play(30);
+
+    assert_eq!(game.game_time(), Some(Duration::SECOND));
+}
+
+#[test]
+fn without_new_game_plus_the_carried_igt_counts() {
+    let mut game = Game::new();
+    game.timer_state = TimerState::NotRunning;
+    game.set_igt(30 * 60 * 60 * 10);
+    game.update();
+    game.start_run();
+    game.play(30);
+
+    assert_eq!(
+        game.game_time(),
+        Some(Duration::hours(10) + Duration::SECOND)
+    );
+}
+
+#[cfg(not(feature = "doors-only"))]
+#[test]
+fn one_frame_of_garbage_in_a_slot() {
+    let mut game = Game::new();
+    game.settings.cardc = true;
+    game.set_slot(2, [338, 1, 0]);
+    game.update();
+    game.set_slot(2, [0, 0, 0]);
+    assert_eq!(game.count_splits(10), 0);
+
+    game.set_slot(2, [338, 1, 0]);
+    assert_eq!(game.count_splits(10), 1);
+}
+
+#[cfg(not(feature = "doors-only"))]
+#[test]
+fn dropped_inventory_read() {
+    let mut game = Game::new();
+    game.settings.cardc = true;
+    game.set_slot(2, [338, 1, 0]);
+    assert_eq!(game.count_splits(3), 1);
+
+    let full = disc_1_dump();
+    let item_1 = Offsets::new().item_1;
+    game.dump.remove(item_1, 12 * 6);
+    game.play(1);
+    let items = game.watchers.inventory.pair.unwrap();
+    assert_eq!(items.current[2], 338);
+    assert!(items.unchanged());
+
+    game.dump
+        .set(item_1, &full.bytes::<{ 12 * 6 }>(item_1).unwrap());
+    game.set_slot(2, [338, 1, 0]);
+    assert_eq!(game.count_splits(3), 0);
+}
+
+// A disc swap can show garbage where the game code is for a few updates. Nothing the game had
+// before is seen as lost and picked up again, or as a move to another map.
+#[cfg(not(feature = "doors-only"))]
+#[test]
+fn disc_swap_through_a_garbage_game_code() {
+    let mut game = Game::new();
+    game.settings.keno = true;
+    game.settings.door_split = true;
+    let gamecode = Offsets::new().gamecode_ntsc;
+    game.dump.set(gamecode, b"\xFF\x00GARBAGE\x13\x37");
+    assert_eq!(game.count_splits(10), 0);
+    game.dump.set(gamecode, b"SLUS_011.99");
+    assert_eq!(game.count_splits(20), 0);
+    assert!(game.watchers.disc == Some(Disc::Two));
+}
+
+#[test]
+fn door_into_the_ending_splits_once() {
+    let mut game = Game::disc_2();
+    game.settings.door_split = true;
+    game.set_map(40);
+    assert_eq!(game.count_splits(5), 1);
+
+    game.set_ending(0xFFFF);
+    game.set_map(123);
+    assert_eq!(game.count_splits(20), 1);
+    assert!(!door_split(&game.watchers, &game.settings));
+}
+
+#[test]
+fn menu_reading_zero_igt() {
+    let mut game = Game::new();
+    game.timer_state = TimerState::NotRunning;
+    game.play(30);
+    let frames = game.igt_frames();
+    game.set_igt(0);
+    for _ in 0..30 {
+        game.update();
+        assert_eq!(
+            game.watchers.igt.pair.unwrap().current,
+            frame_count::<30>(frames as u64)
+        );
+        assert!(!start(&game.watchers, &game.settings, None));
+    }
+    game.set_igt(frames);
+    game.update();
+    assert!(!start(&game.watchers, &game.settings, None));
+}
+
+#[test]
+fn long_menu_reading_zero_igt() {
+    let mut game = Game::new();
+    let before = game.game_time().unwrap();
+    let frames = game.igt_frames();
+    game.set_igt(0);
+    for _ in 0..ZERO_IGT_HOLD_UPDATES + 10 {
+        game.update();
+    }
+    assert_eq!(game.game_time(), Some(before));
+    game.set_igt(frames);
+    game.update();
+    game.play(30);
+    assert!(!start(&game.watchers, &game.settings, None));
+    assert_eq!(game.game_time().unwrap() - before, Duration::SECOND);
+}
+
+#[test]
+fn new_game_after_quitting_on_the_same_map() {
+    let mut game = Game::new();
+    game.timer_state = TimerState::NotRunning;
+    game.set_igt(0);
+    for _ in 0..ZERO_IGT_HOLD_UPDATES + 1 {
+        game.update();
+    }
+    assert_eq!(game.watchers.igt.pair.unwrap().current, Duration::ZERO);
+    game.set_igt(1);
+    game.update();
+    assert!(start(&game.watchers, &game.settings, None));
+}
+
+// Loading a save from the title screen isn't a new game, so leaving its room doesn't start the
+// timer. Leaving the opening map of a new game does.
+#[test]
+fn gain_of_control_only_after_a_new_game() {
+    let mut game = Game::new();
+    game.settings.start_mode = StartMode::GainOfControl;
+    game.timer_state = TimerState::NotRunning;
+    let mut starts = |igt, map| {
+        game.set_igt(0);
+        for _ in 0..ZERO_IGT_HOLD_UPDATES + 1 {
+            game.update();
+        }
+        game.set_igt(igt);
+        game.play(30);
+        game.set_map(map);
+        (0..5).any(|_| {
+            game.update();
+            start(&game.watchers, &game.settings, None)
+        })
+    };
+    assert!(!starts(30 * 60 * 10, 40));
+    assert!(starts(1, 12));
+}
+
+// The chosen room start fires on entering the room, and never without a room chosen
+#[test]
+fn start_on_a_chosen_room() {
+    let mut game = Game::new();
+    game.settings.start_mode = StartMode::ChosenRoom;
+    game.timer_state = TimerState::NotRunning;
+    let mut starts = |map, start_map| {
+        game.set_map(map);
+        (0..5).any(|_| {
+            game.update();
+            start(&game.watchers, &game.settings, start_map)
+        })
+    };
+    assert!(!starts(40, Some(41)));
+    assert!(starts(41, Some(41)));
+    assert!(!starts(40, None));
+    assert!(!starts(41, None));
+}
+
+// Splits are published with the game time LiveSplit is given, and with the run's IGT when
+// LiveSplit keeps the time itself
+#[test]
+fn split_times_follow_the_game_time() {
+    let mut game = Game::new();
+    game.settings.whole_seconds = true;
+    game.play(15);
+    assert_eq!(
+        split_time(&game.watchers, &game.settings),
+        Some(Duration::seconds(65))
+    );
+    game.settings.timing_mode = TimingMode::RtaNoLoads;
+    assert_eq!(
+        split_time(&game.watchers, &game.settings),
+        Some(Duration::milliseconds(65_500))
+    );
+}
+
+// Attaching the way main() does while the timer is already running. Game time counts from the
+// first IGT read instead of jumping to the file's whole IGT, and carries on across a re-attach.
+#[test]
+fn attach_mid_run() {
+    let mut game = Game {
+        dump: disc_1_dump(),
+        watchers: carry_run_state(Watchers::default()),
+        settings: settings(),
+        timer_state: TimerState::Running,
+    };
+    game.update();
+    game.update();
+    assert_eq!(game.game_time(), Some(Duration::ZERO));
+    game.play(30);
+    assert_eq!(game.game_time(), Some(Duration::SECOND));
+
+    game.watchers = carry_run_state(core::mem::take(&mut game.watchers));
+    game.update();
+    game.play(30);
+    assert_eq!(game.game_time(), Some(Duration::SECOND * 2));
+}
+
+// A run started by hand a second into a new game, with the auto start off. It gets the same setup
+// as an auto start, so game time is the file's IGT, or with NG+ only the IGT since the start.
+#[test]
+fn manually_started_run() {
+    for new_game_plus in [false, true] {
+        let mut game = Game::new();
+        game.settings.start = false;
+        game.settings.new_game_plus = new_game_plus;
+        game.timer_state = TimerState::NotRunning;
+        game.set_igt(0);
+        game.update();
+        game.play(30);
+        assert!(!start(&game.watchers, &game.settings, None));
+
+        game.start_run();
+        game.play(60);
+        let expected = if new_game_plus { 60 } else { 90 };
+        assert_eq!(game.game_time(), Some(frame_count::<30>(expected)));
+    }
+}
+
+// The ending value from the last attempt can still be set as the next one starts. Only setting it
+// again during the new attempt counts towards the end split and the credits.
+#[test]
+fn ending_left_over_from_the_last_attempt() {
+    let mut game = Game::disc_2();
+    game.settings.credits = true;
+    game.set_ending(0xFFFF);
+    game.set_map(123);
+    assert_eq!(count_endings(&mut game, 20), 1);
+
+    game.timer_state = TimerState::NotRunning;
+    game.play(5);
+    game.start_run();
+    game.set_map(40);
+    assert_eq!(game.count_splits(5), 0);
+    game.set_map(123);
+    assert_eq!(count_endings(&mut game, 20), 0);
+    game.set_map(40);
+    assert_eq!(game.count_splits(5), 0);
+
+    game.set_ending(0);
+    game.play(5);
+    game.set_map(123);
+    game.set_ending(0xFFFF);
+    assert_eq!(count_endings(&mut game, 20), 1);
+}
+
+// LiveSplit is only told to pause or resume game time as a load starts or ends, not every update
+#[test]
+fn game_time_paused_on_changes_only() {
+    let mut game = Game::new();
+    game.settings.timing_mode = TimingMode::RtaNoLoads;
+    runtime::timer::game_time_calls();
+    for _ in 0..2 {
+        for _ in 0..90 {
+            game.play(1);
+            pause_for_loads(&mut game.watchers, &game.settings);
+        }
+        for _ in 0..LOAD_FREEZE_UPDATES * 2 {
+            game.update();
+            pause_for_loads(&mut game.watchers, &game.settings);
+        }
+    }
+    game.play(1);
+    pause_for_loads(&mut game.watchers, &game.settings);
+
+    assert_eq!(
+        runtime::timer::game_time_calls(),
+        [false, true, false, true, false]
+    );
+}
+
+// The fixture's 1950 frames show as 1:05 on the in-game clock, and keep doing so until the 66th
+// second is full
+#[test]
+fn whole_seconds_match_the_in_game_clock() {
+    let mut game = Game::new();
+    game.settings.whole_seconds = true;
+    assert_eq!(game.game_time(), Some(Duration::seconds(65)));
+    game.play(29);
+    assert_eq!(game.game_time(), Some(Duration::seconds(65)));
+    game.play(1);
+    assert_eq!(game.game_time(), Some(Duration::seconds(66)));
+}
+
+// Both of these set game time back on purpose, which mustn't be taken for the accounting going
+// wrong
+#[test]
+fn game_time_settings_changed_mid_run() {
+    let mut game = Game::new();
+    game.settings.pause_on_death = true;
+    game.play(15);
+    game.game_time();
+    game.settings.whole_seconds = true;
+    game.game_time();
+    game.settings.whole_seconds = false;
+    game.watchers.run.died_at = run_igt(&game.watchers);
+    game.play(30);
+    game.game_time();
+    game.settings.pause_on_death = false;
+    assert!(game.game_time().is_some());
+}
+
+// The raw IGT mode reports the counter itself, so a loaded save sets game time back with it, where
+// the default mode carries the time played before the rewind on
+#[test]
+fn raw_igt_goes_back_with_a_loaded_save() {
+    let mut game = Game::new();
+    game.settings.timing_mode = TimingMode::RawIgt;
+    assert_eq!(is_loading(&game.watchers, &game.settings), Some(true));
+    game.set_igt(3000);
+    game.play(300);
+    assert_eq!(game.game_time(), Some(Duration::seconds(110)));
+    game.set_igt(1500);
+    game.play(30);
+    assert_eq!(game.game_time(), Some(Duration::seconds(51)));
+
+    game.settings.timing_mode = TimingMode::Igt;
+    assert!(game.game_time() > Some(Duration::seconds(110)));
+}
+
+#[test]
+#[should_panic(expected = "game time went backwards")]
+fn game_time_going_backwards_is_caught() {
+    let mut game = Game::new();
+    game.play(30);
+    game.game_time();
+    game.watchers.run.accumulated_igt -= Duration::SECOND;
+    game.game_time();
+}
+
+#[test]
+#[should_panic(expected = "run IGT went negative")]
+fn negative_run_igt_is_caught() {
+    let mut game = Game::new();
+    game.watchers.run.buffer_igt = Duration::hours(1);
+    game.play(1);
+}
+
+// The game playing on through a LiveSplit pause. Game time stands still for the whole pause, then
+// counts on from where it stopped once the timer is resumed.
+#[test]
+fn game_played_through_a_timer_pause() {
+    let mut game = Game::new();
+    let start = game.game_time().unwrap();
+    game.play(30);
+    // Updates run several times per IGT frame, so the pause is seen before the next one
+    game.timer_state = TimerState::Paused;
+    game.update();
+    for _ in 0..3 {
+        game.play(30);
+        assert_eq!(game.game_time().unwrap() - start, Duration::SECOND);
+    }
+    game.timer_state = TimerState::Running;
+    game.update();
+    game.play(30);
+
+    assert_eq!(game.game_time().unwrap() - start, Duration::SECOND * 2);
+}
+
+#[test]
+fn stopped_timer_clears_the_run() {
+    let mut run = RunState {
+        accumulated_igt: Duration::minutes(3),
+        buffer_igt: Duration::minutes(1),
+        ending_age: Some(2),
+        ending_reached: true,
+        splits: 4,
+        ..RunState::default()
+    };
+
+    let igt = Some(Duration::minutes(2));
+    assert!(sync_run_state(&mut run, TimerState::Running, igt).is_none());
+    assert_eq!(run.splits, 4);
+
+    let previous = sync_run_state(&mut run, TimerState::NotRunning, igt).unwrap();
+    assert_eq!(previous.splits, 4);
+    assert_eq!(previous.accumulated_igt, Duration::minutes(3));
+    assert_eq!(run.splits, 0);
+    assert_eq!(run.accumulated_igt, Duration::ZERO);
+    assert_eq!(run.buffer_igt, Duration::ZERO);
+    assert!(run.ending_age.is_none() && !run.ending_reached);
+}
+
+#[test]
+fn timer_pause_is_left_out() {
+    let mut run = RunState::default();
+    let at = |seconds| Some(Duration::seconds(seconds));
+    sync_run_state(&mut run, TimerState::Running, at(10));
+    sync_run_state(&mut run, TimerState::Paused, at(11));
+    sync_run_state(&mut run, TimerState::Paused, at(14));
+    assert_eq!(run.timer_paused_at, Some(Duration::seconds(11)));
+    sync_run_state(&mut run, TimerState::Running, at(16));
+
+    assert_eq!(run.timer_paused_at, None);
+    assert_eq!(run.timer_paused_igt, Duration::seconds(5));
+}
+
+#[test]
+fn death_through_a_timer_pause() {
+    let mut run = RunState {
+        died_at: Some(Duration::seconds(8)),
+        ..RunState::default()
+    };
+    let at = |seconds| Some(Duration::seconds(seconds));
+    sync_run_state(&mut run, TimerState::Paused, at(10));
+    sync_run_state(&mut run, TimerState::Running, at(13));
+
+    // Counted up to the pause, and from where it ended on
+    assert_eq!(run.death_igt, Duration::seconds(2));
+    assert_eq!(run.died_at, Some(Duration::seconds(13)));
+}
+
+#[test]
+fn game_code_variants() {
+    for code in [
+        b"SLUS_008.98",
+        b"slus-008.98",
+        b"SLUS 008.98",
+        b"SLUS_00898\0",
+        b"SLUS00898  ",
+    ] {
+        assert_eq!(&normalize_gamecode(*code), b"SLUS00898");
+    }
+    assert_eq!(&normalize_gamecode(*b"SLUS_011.99"), b"SLUS01199");
+    assert_eq!(&normalize_gamecode(*b"SLUS_0089\0\0"), b"SLUS0089\0");
+}
+
+#[test]
+fn padded_game_code_attaches() {
+    let mut dump = disc_1_dump();
+    dump.set(Offsets::new().gamecode_ntsc, b"SLUS-00898 ");
+    let mut watchers = Watchers::default();
+    assert!(run_updates(&dump, &mut watchers, &settings(), 2));
+    assert!(watchers.game_confirmed);
+}
+
+#[test]
+fn settings_code_has_its_version() {
+    let mut settings = settings();
+    let code = SettingsCode(&settings).to_string();
+    assert!(code.starts_with("1."));
+
+    // The first checkbox is the lowest bit of the first digit after the dash
+    let dash = code.find('-').unwrap();
+    settings.pause_on_stale = false;
+    let changed = SettingsCode(&settings).to_string();
+    assert_eq!(code[..=dash], changed[..=dash]);
+    assert_eq!(&code[dash + 1..dash + 2], "1");
+    assert_eq!(&changed[dash + 1..dash + 2], "0");
+}
+
+#[cfg(not(feature = "doors-only"))]
+#[test]
+fn key_used() {
+    let mut game = Game::new();
+    game.settings.evil = true;
+    game.settings.key_split = items::KeySplit::Use;
+    game.set_slot(2, [340, 1, 0]);
+    assert_eq!(game.count_splits(5), 0);
+    game.set_slot(2, [EMPTY_SLOT, 0, 0]);
+    assert_eq!(game.count_splits(5), 1);
+}
+
+#[cfg(not(feature = "doors-only"))]
+#[test]
+fn key_taken_out_by_a_loaded_save() {
+    let mut game = Game::new();
+    game.settings.evil = true;
+    game.settings.key_split = items::KeySplit::Use;
+    game.set_slot(2, [340, 1, 0]);
+    game.play(300);
+    game.set_igt(game.igt_frames() - 200);
+    game.set_slot(2, [EMPTY_SLOT, 0, 0]);
+    assert_eq!(game.count_splits(10), 0);
+
+    // Picked up again and used for real this time
+    game.set_slot(2, [340, 1, 0]);
+    game.play(10);
+    game.set_slot(2, [EMPTY_SLOT, 0, 0]);
+    assert_eq!(game.count_splits(5), 1);
+}
+
+// Picks the keys up, uses them and enters the next room, counting the splits on the use and on
+// the room change
+#[cfg(not(feature = "doors-only"))]
+fn use_keys_then_change_rooms(setup: impl Fn(&mut Settings), keys: &[u16]) -> (u32, u32) {
+    let mut game = Game::new();
+    setup(&mut game.settings);
+    for (slot, &key) in (2..).zip(keys) {
+        game.set_slot(slot, [key, 1, 0]);
+    }
+    game.play(5);
+    for slot in (2..).take(keys.len()) {
+        game.set_slot(slot, [EMPTY_SLOT, 0, 0]);
+    }
+    let used = game.count_splits(5);
+    game.set_map(13);
+    (used, game.count_splits(5))
+}
+
+// The settings going by the room entered after a key split on the next map change, not on the
+// key being used, and only once
+#[cfg(not(feature = "doors-only"))]
+#[test]
+fn room_entered_after_a_key() {
+    let key_entry = |settings: &mut Settings| {
+        settings.evil = true;
+        settings.key_split = items::KeySplit::Entry;
+    };
+    assert_eq!(use_keys_then_change_rooms(key_entry, &[340]), (0, 1));
+    let evil_house = |settings: &mut Settings| settings.evil_house = true;
+    assert_eq!(use_keys_then_change_rooms(evil_house, &[340]), (0, 1));
+    let moon_room = |settings: &mut Settings| settings.moon_room = true;
+    assert_eq!(use_keys_then_change_rooms(moon_room, &[337]), (0, 1));
+    let park_areas = |settings: &mut Settings| settings.park_areas = true;
+    assert_eq!(
+        use_keys_then_change_rooms(park_areas, &[335, 336, 337]),
+        (0, 1)
+    );
+    let sin_used = |settings: &mut Settings| settings.sin_used = true;
+    assert_eq!(use_keys_then_change_rooms(sin_used, &[423]), (1, 0));
+}
+
+#[test]
+fn door_split_queue() {
+    let mut run = RunState::default();
+    queue_splits(&mut run, 2, None);
+    queue_splits(&mut run, 2, Some(1));
+    assert_eq!(take_split(&mut run), Some(false));
+    assert_eq!(take_split(&mut run), Some(false));
+    assert_eq!(take_split(&mut run), Some(false));
+    assert_eq!(take_split(&mut run), Some(true));
+    assert_eq!(take_split(&mut run), None);
+    assert_eq!(run.queued_doors, 0);
+}
+
+// Two key items picked up in the same update, then a door on the next. Each gets its own split,
+// one per update, and the door's time is only taken on the update it splits on.
+#[cfg(not(feature = "doors-only"))]
+#[test]
+fn coincident_splits() {
+    let mut game = Game::new();
+    game.settings.door_split = true;
+    game.settings.cardc = true;
+    game.settings.cardd = true;
+    game.set_slot(2, [338, 1, 0]);
+    game.set_slot(3, [339, 1, 0]);
+    // A new item takes an update to settle
+    assert_eq!(game.take_splits(2), [false, true]);
+
+    game.set_map(13);
+    assert_eq!(game.take_splits(1), [true]);
+    assert_eq!(game.watchers.run.last_door_split, None);
+
+    let door_time = {
+        game.play(1);
+        run_igt(&game.watchers)
+    };
+    assert!(next_split(&mut game.watchers, &game.settings));
+    assert_eq!(game.watchers.run.last_door_split, door_time);
+    assert_eq!(game.take_splits(5), [false; 5]);
+}